@@ -0,0 +1,49 @@
+use voxbox::*;
+
+// Phrases shaped by breath instead of an output envelope:
+// a lung model (Balloon) drives the subglottal pressure, so
+// voicing starts and stops on its own as the pressure
+// crosses the phonation threshold, with breathy onsets and
+// releases. The reverb tail is left alone.
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("breath.wav");
+
+    let mut voice = VoiceBuilder::new(sr)
+        .voice_type(VoiceType::Tenor)
+        .build();
+
+    let mut lungs = Balloon::new(sr);
+    lungs.inflation = 0.3;
+    lungs.deflation = 0.8;
+    voice.set_lungs(lungs, 1400.0);
+
+    let mut reverb = BigVerb::new(sr);
+    reverb.size = 0.9;
+    let mut dcblk = DCBlocker::new(sr);
+
+    // (note, breath, duration in seconds)
+    let phrase = [
+        (57.0, 1.0, 1.0),
+        (59.0, 1.0, 0.5),
+        (60.0, 1.2, 1.5),
+        (60.0, 0.0, 1.0),
+        (64.0, 0.8, 0.6),
+        (62.0, 0.6, 1.2),
+        (62.0, 0.0, 2.0),
+    ];
+
+    for (note, breath, dur) in phrase {
+        voice.pitch = note;
+        voice.set_breath(breath);
+
+        for _ in 0 .. (sr as f32 * dur) as usize {
+            let out = voice.tick() * 0.4;
+            let (rvb, _) = reverb.tick(out, out);
+            let rvb = dcblk.tick(rvb);
+            wav.tick(out + rvb * 0.15);
+        }
+    }
+}
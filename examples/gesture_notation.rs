@@ -0,0 +1,50 @@
+use voxbox::*;
+
+// The melody from gesture_builder.rs, written in the
+// gesture path notation instead of by hand, and played
+// twice. See notation.rs for the syntax.
+
+// rates are vertices per beat: 2 is an eighth note,
+// 4 a sixteenth and 4/3 a dotted eighth
+const MELODY: &str = "
+    # 66 is f#4
+    |:
+        f#4 2 gm; g#4; a4; c#5
+        f#4; g#4 4; a4 4/3; c#5 2
+        f#4 gh; c#5 gm; b4; a4; b4
+        a4 4; b4 4/3; a4 2 gl
+    :|
+    r 1/2 step
+";
+
+fn main() {
+    let sr = 44100;
+
+    let path = match GesturePath::parse(MELODY) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let mut wav = MonoWav::new("gesture_notation.wav");
+    let mut voice = Voice::new(sr, 13.0, 2);
+    let mut reverb = BigVerb::new(sr);
+    let mut clk = Phasor::new(sr, 0.0);
+    let bpm = 98.0;
+
+    clk.set_freq(bpm / 60.0);
+    voice.glottis.set_aspiration(0.3);
+    voice.tract.drm(&[1.011, 0.201, 0.487, 0.440, 1.297, 2.368, 1.059, 2.225]);
+
+    let mut gst = path.builder();
+    let dur = path.duration() * 60.0 / bpm;
+
+    for _ in 0..(sr as f32 * dur) as usize {
+        voice.pitch = gst.tick(clk.tick());
+        let out = voice.tick() * 0.5;
+        let (rvb, _) = reverb.tick(out, out);
+        wav.tick(out + rvb * 0.1);
+    }
+}
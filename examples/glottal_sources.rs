@@ -0,0 +1,48 @@
+use voxbox::*;
+
+// Renders the same vowel with each of the glottal source
+// models, one after the other, for comparison.
+
+fn render<G: GlottalSource>(wav: &mut MonoWav, mut voice: Voice<G>, sr: usize) {
+    let shape = [1.011, 0.201, 0.487, 0.440, 1.297, 2.368, 1.059, 2.225];
+
+    voice.tract.drm(&shape);
+    voice.pitch = 55.0;
+
+    for _ in 0..(sr as f32 * 2.0) as usize {
+        let out = voice.tick() * 0.4;
+        wav.tick(out);
+    }
+}
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 17.0;
+
+    let mut wav = MonoWav::new("glottal_sources.wav");
+
+    render(&mut wav, Voice::new(sr, tract_len, oversample), sr);
+
+    let rosenberg = Rosenberg::new(sr);
+    render(
+        &mut wav,
+        Voice::with_source(sr, tract_len, oversample, rosenberg),
+        sr,
+    );
+
+    let mut klglott = KLGlott88::new(sr);
+    klglott.set_tilt(6.0);
+    render(
+        &mut wav,
+        Voice::with_source(sr, tract_len, oversample, klglott),
+        sr,
+    );
+
+    let twomass = TwoMass::new(sr);
+    render(
+        &mut wav,
+        Voice::with_source(sr, tract_len, oversample, twomass),
+        sr,
+    );
+}
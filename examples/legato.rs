@@ -0,0 +1,47 @@
+use voxbox::*;
+
+// A legato phrase played with the note API: overlapping notes
+// glide into each other with a singer-like portamento (a small
+// preparation dip and overshoot), detached notes start afresh.
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("legato.wav");
+
+    let mut voice = VoiceBuilder::new(sr)
+        .voice_type(VoiceType::MezzoSoprano)
+        .build();
+
+    voice.set_portamento(120.0);
+    voice.portamento.set_preparation(0.1);
+    voice.portamento.set_overshoot(0.08);
+    voice.vibrato.set_onset(0.3, 0.5);
+
+    // (note, start, end) in seconds. Notes that overlap are legato.
+    let notes = [
+        (62.0, 0.0, 0.65),
+        (69.0, 0.6, 1.45),
+        (67.0, 1.4, 1.9),
+        (65.0, 1.85, 2.4),
+        (64.0, 2.6, 3.0),
+        (65.0, 3.0, 3.2),
+        (62.0, 3.15, 4.5),
+    ];
+
+    let sample = |t: f32| (t * sr as f32) as usize;
+
+    for n in 0..sample(5.0) {
+        for &(note, start, end) in notes.iter() {
+            if n == sample(start) {
+                voice.note_on(note, 0.7);
+            }
+
+            if n == sample(end) {
+                voice.note_off(note);
+            }
+        }
+
+        wav.tick(voice.tick() * 0.5);
+    }
+}
@@ -0,0 +1,45 @@
+use voxbox::*;
+
+// Sings "hello, sweet summer" on a short melody, using the
+// phoneme sequencer for the articulation. The pitch is set
+// separately, one note per syllable.
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("lyrics.wav");
+
+    let mut voice = VoiceBuilder::new(sr)
+        .voice_type(VoiceType::Tenor)
+        .vibrato(5.8, 0.2)
+        .build();
+
+    // syllables, and the note they are sung on
+    let syllables = [
+        ("HH:0.08 AH:0.3 L:0.07", 60.0),
+        ("OW:0.6 _:0.15", 64.0),
+        ("S:0.12 W:0.06 IY:0.5 T:0.08 _:0.1", 67.0),
+        ("S:0.12 AH:0.3 M:0.1", 65.0),
+        ("ER:0.8 _:0.4", 64.0),
+    ];
+
+    // one timeline for the whole phrase, so the syllables
+    // are coarticulated too
+    let text: Vec<&str> = syllables.iter().map(|s| s.0).collect();
+    let timeline = PhonemeTimeline::parse(&text.join(" ")).unwrap();
+    let mut seq = PhonemeSequencer::new(sr, timeline);
+
+    let mut start = 0.0;
+
+    for (text, note) in syllables {
+        let end = start + PhonemeTimeline::parse(text).unwrap().duration();
+        voice.pitch = note;
+        voice.onset();
+
+        while seq.time() < end && !seq.done() {
+            wav.tick(seq.tick(&mut voice) * 0.5);
+        }
+
+        start = end;
+    }
+}
@@ -0,0 +1,31 @@
+use voxbox::*;
+use std::f32::consts::PI;
+
+// A single sustained note that swells and fades, driven
+// entirely by vocal effort.
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 14.0;
+    let dur = 8.0;
+
+    let mut wav = MonoWav::new("messa_di_voce.wav");
+    let mut voice = Voice::new(sr, tract_len, oversample);
+
+    let shape = [0.768, 0.5, 0.5, 0.5, 1.454, 3.368, 3.082, 2.74];
+    voice.tract.drm(&shape);
+    voice.pitch = 67.0;
+    voice.vibrato_rate(5.8);
+    voice.vibrato_depth(0.15);
+
+    let nsamps = (sr as f32 * dur) as usize;
+
+    for n in 0..nsamps {
+        let t = n as f32 / nsamps as f32;
+        let effort = (PI * t).sin();
+        voice.glottis.set_effort(effort);
+        let out = voice.tick() * 0.2;
+        wav.tick(out);
+    }
+}
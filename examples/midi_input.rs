@@ -0,0 +1,62 @@
+use voxbox::*;
+
+// Plays a voice from a raw MIDI byte stream, the way a host
+// would: audio is rendered in blocks, and the bytes that came
+// in during a block are queued with their offset in it. Here
+// the "incoming" bytes are a made up phrase, with a pitch bend
+// scoop, mod wheel vibrato and a vowel change through the
+// tongue controllers.
+
+const BLOCK: usize = 64;
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("midi_input.wav");
+
+    let mut voice = VoiceBuilder::new(sr)
+        .voice_type(VoiceType::Baritone)
+        .build();
+    voice.vibrato.set_onset(0.3, 0.4);
+
+    let mut input = MidiInput::new();
+
+    // (time in seconds, bytes)
+    let stream: Vec<(f32, Vec<u8>)> = vec![
+        (0.0, vec![0xb0, CC_TONGUE_POSITION, 20, CC_TONGUE_DIAMETER, 90]),
+        (0.0, vec![0xe0, 0x00, 0x30, 0x90, 50, 80]),
+        // bend back up to the note, running status
+        (0.05, vec![0xe0, 0x00, 0x38]),
+        (0.1, vec![0x00, 0x40]),
+        (0.8, vec![0xb0, CC_MODULATION, 50]),
+        (1.2, vec![0x80, 50, 0, 0x90, 55, 100]),
+        (1.6, vec![0xb0, CC_TONGUE_POSITION, 100, CC_TONGUE_DIAMETER, 40]),
+        (2.6, vec![0x90, 53, 90, 0x80, 55, 0]),
+        (3.0, vec![0xb0, CC_VELUM, 127]),
+        (3.8, vec![0x80, 53, 0]),
+    ];
+
+    let mut next = 0;
+    let nblocks = (sr as f32 * 4.5) as usize / BLOCK;
+
+    for block in 0..nblocks {
+        let start = block * BLOCK;
+
+        // bytes arriving during this block
+        while let Some((time, bytes)) = stream.get(next) {
+            let t = (time * sr as f32) as usize;
+
+            if t >= start + BLOCK {
+                break;
+            }
+
+            input.push(t.saturating_sub(start), bytes);
+            next += 1;
+        }
+
+        for _ in 0..BLOCK {
+            input.tick(&mut voice);
+            wav.tick(voice.tick() * 0.3);
+        }
+    }
+}
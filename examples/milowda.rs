@@ -51,11 +51,6 @@ fn pitch_gestures(chords: &[[i16; 4]]) -> Vec<Vec<GestureVertex<f32>>> {
 
 fn main() {
     let sr = 44100;
-    let oversample = 2;
-    let tract_cm_tenor = 16.0;
-    let tract_cm_bass = 18.3;
-    let tract_cm_alto = 14.;
-    let tract_cm_soprano = 12.3;
     //let chord = [0, 7, 0, 4];
     //let chord = [0, 9, 2, 7];
     let base_pitch = 58;
@@ -109,61 +104,10 @@ fn main() {
     let mut gst_bas = LinearGesture::new();
     gst_bas.init(&paths[0]);
 
-    let mut alto = Voice::new(sr, tract_cm_alto, oversample);
-    let mut bass = Voice::new(sr, tract_cm_bass, oversample);
-    let mut tenor = Voice::new(sr, tract_cm_tenor, oversample);
-    let mut soprano = Voice::new(sr, tract_cm_soprano, oversample);
-
-    let shape_ah_alto = [
-        // 1.225,
-        // 0.225,
-        // 0.392,
-        // 0.5,
-        // 1.13,
-        // 2.059,
-        // 0.297,
-        // 1.392
-        0.768, 0.5, 0.5, 0.5, 1.454, 3.368, 3.082, 2.74,
-    ];
-
-    let shape_ah_sop = [1.773, 0.225, 0.392, 0.5, 1.868, 1.987, 0.392, 3.249];
-
-    let shape_ah_tenor = [0.225, 0.059, 0.059, 0.082, 0.701, 3.701, 1.725, 1.082];
-
-    let shape_ah_bass = [0.225, 0.63, 0.844, 0.5, 0.5, 3.701, 0.82, 2.106];
-
-    tenor.tract.drm(&shape_ah_tenor);
-    //tenor.glottis.set_pitch((base_pitch + chord[1] - 12) as f32);
-    //tenor.glottis.set_pitch_ji((base_pitch - 12) as f32, 4);
-    tenor.glottis.set_shape(0.3);
-    tenor.glottis.srand(12345);
-    tenor.vibrato_rate(6.1);
-    tenor.vibrato_depth(0.2);
-    tenor.glottis.set_aspiration(0.04);
-
-    bass.tract.drm(&shape_ah_bass);
-    bass.vibrato_rate(6.1);
-    bass.vibrato_depth(0.1);
-    //bass.glottis.set_pitch((base_pitch + chord[0] - 12) as f32);
-
-    //bass.glottis.set_pitch_ji((base_pitch - 12) as f32, 0);
-    bass.glottis.set_shape(0.2);
-    bass.glottis.srand(54321);
-    bass.glottis.set_aspiration(0.04);
-
-    alto.tract.drm(&shape_ah_alto);
-    alto.vibrato_rate(6.0);
-    alto.vibrato_depth(0.1);
-    alto.glottis.set_shape(0.3);
-    alto.glottis.set_aspiration(0.05);
-    alto.glottis.srand(330303);
-
-    soprano.tract.drm(&shape_ah_sop);
-    soprano.glottis.set_shape(0.4);
-    soprano.glottis.set_aspiration(0.1);
-    soprano.glottis.srand(111111);
-    soprano.vibrato_rate(6.5);
-    soprano.vibrato_depth(0.05);
+    let mut alto = VoiceBuilder::new(sr).voice_type(VoiceType::Alto).build();
+    let mut bass = VoiceBuilder::new(sr).voice_type(VoiceType::Bass).build();
+    let mut tenor = VoiceBuilder::new(sr).voice_type(VoiceType::Tenor).build();
+    let mut soprano = VoiceBuilder::new(sr).voice_type(VoiceType::Soprano).build();
 
     reverb.size = 0.93;
 
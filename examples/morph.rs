@@ -0,0 +1,58 @@
+use voxbox::*;
+
+// Morphs between DRM tract shapes with a gesture over
+// [f32; 8], driven by the same clock as the pitch gesture.
+// Shape changes line up with the notes, and use the same
+// rate multipliers and behaviors.
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("morph.wav");
+    let mut voice = Voice::new(sr, 15.0, 2);
+    let mut clk = Phasor::new(sr, 0.0);
+    clk.set_freq(84.0 / 60.0);
+
+    let ah = [1.011, 0.201, 0.487, 0.440, 1.297, 2.368, 1.059, 2.225];
+    let ee = [1.035, 0.201, 0.487, 0.440, 0.178, 0.249, 0.463, 2.249];
+    let oo = [3.344, 0.44, 0.463, 0.5, 3.154, 3.225, 0.416, 0.463];
+    let eh = [0.768, 0.5, 0.5, 0.5, 1.454, 3.368, 3.082, 2.74];
+
+    // (note, shape, num, den, pitch behavior, shape behavior)
+    let phrase = [
+        (62.0, ah, 1, 1, Behavior::GlissMedium, Behavior::Smoothstep),
+        (65.0, ee, 1, 1, Behavior::GlissSmall, Behavior::Sigmoid(12.0)),
+        (69.0, oo, 1, 2, Behavior::Gliss(0.6, 2.0), Behavior::Linear),
+        (67.0, eh, 2, 1, Behavior::GlissTiny, Behavior::Exponential(3.0)),
+        (65.0, ee, 2, 1, Behavior::GlissMedium, Behavior::Bezier(0.4, 0.0, 0.2, 1.0)),
+        (62.0, ah, 1, 1, Behavior::Step, Behavior::Smoothstep),
+    ];
+
+    let mut pitch = LinearGestureBuilder::new();
+    let mut shape = LinearGestureBuilder::<[f32; 8]>::new();
+
+    for &(nn, drm, num, den, pb, sb) in phrase.iter() {
+        pitch.append(GestureVertex {
+            val: nn,
+            num,
+            den,
+            bhvr: pb,
+        });
+        shape.append(GestureVertex {
+            val: drm,
+            num,
+            den,
+            bhvr: sb,
+        });
+    }
+
+    pitch.done();
+    shape.done();
+
+    for _ in 0..(sr as f32 * 7.0) as usize {
+        let c = clk.tick();
+        voice.pitch = pitch.tick(c);
+        voice.tract.drm(&shape.tick(c));
+        wav.tick(voice.tick() * 0.5);
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+use std::time::Instant;
+use voxbox::*;
+
+// Two voices controlled over OSC on port 7770, rendered to
+// osc_server.wav in real time for 20 seconds. Needs the
+// osc feature:
+//
+// cargo run --features osc --example osc_server
+//
+// From SuperCollider, for example:
+//
+// n = NetAddr("127.0.0.1", 7770);
+// n.sendMsg("/voice/1/pitch", 55);
+// n.sendMsg("/voice/*/tract/tongue", 0.2, 0.8);
+// n.sendMsg("/voice/{1,2}/glot/effort", 0.7);
+// n.sendMsg("/reverb/size", 0.97);
+
+const BLOCK: usize = 256;
+
+fn main() {
+    let sr = 44100;
+
+    let server = OscServer::bind(7770).unwrap();
+    println!("listening on {}", server.local_addr());
+
+    let mut voices = vec![
+        VoiceBuilder::new(sr).voice_type(VoiceType::Tenor).pitch(55.0).build(),
+        VoiceBuilder::new(sr).voice_type(VoiceType::Alto).pitch(62.0).build(),
+    ];
+
+    let mut reverb = BigVerb::new(sr);
+    let mut wav = MonoWav::new("osc_server.wav");
+
+    let start = Instant::now();
+    let nblocks = 20 * sr / BLOCK;
+
+    for block in 0..nblocks {
+        for err in server.apply(&mut voices, &mut reverb) {
+            println!("{}", err);
+        }
+
+        for _ in 0..BLOCK {
+            let dry: f32 = voices.iter_mut().map(|v| v.tick()).sum::<f32>() * 0.3;
+            let (rvb, _) = reverb.tick(dry, dry);
+            wav.tick(dry + rvb * 0.2);
+        }
+
+        // wait for the audio to catch up with the clock
+        let due = Duration::from_secs_f32(((block + 1) * BLOCK) as f32 / sr as f32);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use voxbox::*;
+
+// One sustained note per phonation mode. Each note starts
+// modal and blends into the mode over its duration.
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 17.0;
+    let dur = 3.0;
+
+    let mut wav = MonoWav::new("phonation_modes.wav");
+    let mut voice = Voice::new(sr, tract_len, oversample);
+
+    let shape = [0.225, 0.63, 0.844, 0.5, 0.5, 3.701, 0.82, 2.106];
+    voice.tract.drm(&shape);
+    voice.pitch = 50.0;
+    voice.vibrato_depth(0.0);
+
+    let nsamps = (sr as f32 * dur) as usize;
+
+    for mode in 0..3 {
+        for n in 0..nsamps {
+            let amt = n as f32 / nsamps as f32;
+
+            match mode {
+                0 => voice.glottis.set_fry(amt),
+                1 => voice.glottis.set_creak(amt),
+                _ => voice.glottis.set_diplophonia(amt),
+            }
+
+            let out = voice.tick() * 0.5;
+            wav.tick(out);
+        }
+
+        voice.glottis.set_fry(0.0);
+        voice.glottis.set_creak(0.0);
+        voice.glottis.set_diplophonia(0.0);
+    }
+}
@@ -0,0 +1,50 @@
+use voxbox::*;
+
+// Uses the glottal phase and closure triggers to do
+// pitch-synchronous processing: a RandomLine driven by the
+// glottal phase changes the tract shape once per period,
+// and the closure trigger is used to count periods.
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 17.0;
+
+    let mut wav = MonoWav::new("pitch_synchronous.wav");
+
+    let mut voice = Voice::new(sr, tract_len, oversample);
+
+    let shape = [
+        1.011, 0.201, 0.487, 0.440,
+        1.297, 2.368, 1.059, 2.225
+    ];
+
+    voice.pitch = 48.0;
+
+    // per-period random wobble of the mouth opening
+    let mut wobble = RandomLine::new();
+    wobble.min = 0.6;
+    wobble.max = 1.4;
+    wobble.seed(4321);
+
+    let mut closures = 0;
+    let mut drm = shape;
+
+    for _ in 0 .. (sr as f32 * 5.0) as usize {
+        // phase is from the previous tick, so the new
+        // shape takes effect at the start of the next period
+        let w = wobble.tick(voice.glottal_phase());
+        drm[7] = shape[7] * w;
+        voice.tract.drm(&drm);
+
+        let out = voice.tick() * 0.5;
+
+        if voice.closure_trigger() > 0.0 {
+            closures += 1;
+        }
+
+        wav.tick(out);
+    }
+
+    println!("{} glottal closures", closures);
+}
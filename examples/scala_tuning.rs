@@ -0,0 +1,69 @@
+use voxbox::*;
+
+// Plays a short phrase twice: once in 12-TET, then again
+// in quarter-comma meantone loaded from Scala data.
+
+const MEANTONE: &str = "! meanquar.scl
+!
+1/4-comma meantone scale. Pietro Aaron's temperament (1523)
+ 12
+!
+ 76.04900
+ 193.15686
+ 310.26471
+ 5/4
+ 503.42157
+ 579.47057
+ 696.57843
+ 25/16
+ 889.73529
+ 1006.84314
+ 1082.89214
+ 2/1
+";
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 14.0;
+
+    let mut wav = MonoWav::new("scala_tuning.wav");
+    let mut voice = Voice::new(sr, tract_len, oversample);
+    let mut clk = Phasor::new(sr, 0.0);
+
+    let scale = Scale::parse(MEANTONE).unwrap();
+    let meantone = Tuning::from_scale(scale).unwrap();
+
+    let shape = [0.768, 0.5, 0.5, 0.5, 1.454, 3.368, 3.082, 2.74];
+    voice.tract.drm(&shape);
+    voice.vibrato_depth(0.05);
+    clk.set_freq(1.0);
+
+    let notes = [60, 64, 67, 72, 67, 64, 60, 60];
+
+    for pass in 0..2 {
+        let mut gst = LinearGestureBuilder::new();
+
+        for nn in notes.iter() {
+            gst.append(GestureVertex {
+                val: *nn as f32,
+                num: 1,
+                den: 1,
+                bhvr: Behavior::GlissSmall,
+            });
+        }
+        gst.done();
+
+        if pass == 1 {
+            voice.set_tuning(meantone.clone());
+        }
+
+        clk.reset();
+
+        for _ in 0..(sr as f32 * notes.len() as f32) as usize {
+            voice.pitch = gst.tick(clk.tick());
+            let out = voice.tick() * 0.4;
+            wav.tick(out);
+        }
+    }
+}
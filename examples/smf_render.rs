@@ -0,0 +1,113 @@
+use voxbox::*;
+
+// Renders a MIDI file to smf_render.wav, one voice per
+// track. Run with a file name:
+//
+// cargo run --example smf_render -- song.mid
+//
+// Without one, a small two part piece with lyrics is made
+// up on the spot and written to smf_render.mid first.
+
+fn vlq(mut val: u32) -> Vec<u8> {
+    let mut out = vec![(val & 0x7f) as u8];
+    val >>= 7;
+
+    while val > 0 {
+        out.insert(0, (val & 0x7f) as u8 | 0x80);
+        val >>= 7;
+    }
+
+    out
+}
+
+// a track from (syllable in ARPAbet, note, length in ticks)
+fn track(name: &str, notes: &[(&str, u8, u32)]) -> Vec<u8> {
+    let mut data = vec![];
+
+    let meta = |data: &mut Vec<u8>, kind: u8, text: &str| {
+        data.extend([0x00, 0xff, kind]);
+        data.extend(vlq(text.len() as u32));
+        data.extend(text.as_bytes());
+    };
+
+    meta(&mut data, 0x03, name);
+
+    for &(lyric, note, len) in notes {
+        if !lyric.is_empty() {
+            meta(&mut data, 0x05, lyric);
+        }
+
+        data.extend([0x00, 0x90, note, 90]);
+        data.extend(vlq(len));
+        data.extend([0x80, note, 0]);
+    }
+
+    data.extend([0x00, 0xff, 0x2f, 0x00]);
+
+    let mut chunk = b"MTrk".to_vec();
+    chunk.extend((data.len() as u32).to_be_bytes());
+    chunk.extend(data);
+    chunk
+}
+
+fn demo() -> Vec<u8> {
+    let mut smf = b"MThd".to_vec();
+    smf.extend(6u32.to_be_bytes());
+    smf.extend(1u16.to_be_bytes());
+    smf.extend(3u16.to_be_bytes());
+    smf.extend(480u16.to_be_bytes());
+
+    // 80bpm
+    let mut tempo = vec![0x00, 0xff, 0x51, 0x03, 0x0b, 0x71, 0xb0];
+    tempo.extend([0x00, 0xff, 0x2f, 0x00]);
+    smf.extend(b"MTrk");
+    smf.extend((tempo.len() as u32).to_be_bytes());
+    smf.extend(tempo);
+
+    smf.extend(track("soprano", &[
+        ("HH AH", 72, 480),
+        ("L OW", 74, 480),
+        ("M AY", 76, 960),
+        ("F R EH N D", 74, 1440),
+        ("", 72, 480),
+    ]));
+
+    smf.extend(track("tenor", &[
+        ("HH AH", 57, 480),
+        ("L OW", 59, 480),
+        ("M AY", 60, 960),
+        ("F R EH N D", 55, 1920),
+    ]));
+
+    smf
+}
+
+fn main() {
+    let sr = 44100;
+
+    let smf = match std::env::args().nth(1) {
+        Some(filename) => Smf::open(&filename).unwrap(),
+        None => {
+            let data = demo();
+            std::fs::write("smf_render.mid", &data).unwrap();
+            Smf::parse(&data).unwrap()
+        }
+    };
+
+    let mut renderer = SmfRenderer::new(sr, &smf);
+
+    // pick a voice type from each part's name
+    for i in 0..renderer.len() {
+        let name = renderer.part_name(i).to_lowercase();
+        let voice_type = VoiceType::ALL
+            .iter()
+            .find(|v| name.contains(v.name()))
+            .copied()
+            .unwrap_or(VoiceType::Alto);
+
+        println!("{}: {}", renderer.part_name(i), voice_type);
+        renderer.set_preset(i, &VoicePreset::new(voice_type));
+    }
+
+    renderer.render("smf_render.wav", 1.0, 0.2);
+}
@@ -0,0 +1,39 @@
+use voxbox::*;
+
+// Sweeps the amount of source-tract interaction from none
+// to full over a rising pitch. With coupling, the glottal
+// flow picks up ripple from the first formant and is skewed
+// more as the pitch approaches it.
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 17.0;
+    let dur = 8.0;
+
+    let mut wav = MonoWav::new("source_tract_coupling.wav");
+
+    let mut voice = Voice::new(sr, tract_len, oversample);
+
+    let shape_ah = [
+        0.225, 0.059, 0.059, 0.082,
+        0.701, 3.701, 1.725, 1.082
+    ];
+
+    voice.tract.drm(&shape_ah);
+    voice.vibrato_depth(0.1);
+
+    let nsamps = (sr as f32 * dur) as usize;
+
+    for n in 0 .. nsamps {
+        let pos = n as f32 / nsamps as f32;
+
+        // coupling goes up twice, once per pitch sweep
+        let sweep = (pos * 2.0).fract();
+        voice.set_coupling(sweep);
+        voice.pitch = 45.0 + 24.0 * sweep;
+
+        let out = voice.tick() * 0.3;
+        wav.tick(out);
+    }
+}
@@ -0,0 +1,39 @@
+use voxbox::*;
+
+// A slow glissando through the range where the first
+// subglottal resonance (around 600Hz) sits. The first half
+// is the plain tract, the second half adds the trachea.
+
+fn main() {
+    let sr = 44100;
+    let oversample = 2;
+    let tract_len = 17.0;
+    let dur = 6.0;
+
+    let mut wav = MonoWav::new("trachea.wav");
+
+    let mut voice = Voice::new(sr, tract_len, oversample);
+
+    let shape_ah = [
+        0.225, 0.059, 0.059, 0.082,
+        0.701, 3.701, 1.725, 1.082
+    ];
+
+    voice.tract.drm(&shape_ah);
+    voice.glottis.set_rd(1.8);
+
+    let nsamps = (sr as f32 * dur) as usize;
+
+    for pass in 0..2 {
+        if pass == 1 {
+            voice.tract.enable_trachea(14.0);
+        }
+
+        for n in 0 .. nsamps {
+            let pos = n as f32 / nsamps as f32;
+            voice.pitch = 55.0 + 24.0 * pos;
+            let out = voice.tick() * 0.4;
+            wav.tick(out);
+        }
+    }
+}
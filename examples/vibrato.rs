@@ -0,0 +1,33 @@
+use voxbox::*;
+
+// A short phrase with a singer-like vibrato: each note
+// starts straight, then the vibrato comes in after a short
+// delay, with some drift in rate and depth.
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("vibrato.wav");
+
+    let mut voice = VoiceBuilder::new(sr)
+        .voice_type(VoiceType::Alto)
+        .vibrato(5.6, 0.35)
+        .build();
+
+    voice.vibrato.set_onset(0.35, 0.5);
+    voice.vibrato.set_rate_jitter(0.1);
+    voice.vibrato.set_depth_jitter(0.3);
+    voice.vibrato.set_shape(VibratoShape::Trapezoid);
+
+    let notes = [62.0, 64.0, 65.0, 69.0, 67.0];
+    let dur = 1.6;
+
+    for note in notes {
+        voice.pitch = note;
+        voice.onset();
+
+        for _ in 0 .. (sr as f32 * dur) as usize {
+            wav.tick(voice.tick() * 0.5);
+        }
+    }
+}
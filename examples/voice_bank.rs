@@ -0,0 +1,57 @@
+use voxbox::*;
+
+// A small choir driven like a keyboard: a VoiceBank of
+// mezzo voices plays a chord progression, with a bit of
+// detune and timing humanization between the singers.
+
+fn main() {
+    let sr = 44100;
+
+    let mut wav = MonoWav::new("voice_bank.wav");
+
+    let preset = VoicePreset::new(VoiceType::MezzoSoprano);
+    let mut bank = VoiceBank::new(sr, 6, &preset);
+    bank.set_detune(12.0);
+    bank.set_humanize(0.04);
+    bank.set_attack(0.15);
+    bank.set_release(0.6);
+    bank.seed(1234);
+
+    let mut reverb = BigVerb::new(sr);
+    reverb.size = 0.93;
+
+    let chords: [&[f32]; 4] = [
+        &[57.0, 60.0, 64.0, 69.0],
+        &[53.0, 57.0, 60.0, 65.0],
+        &[55.0, 59.0, 62.0, 67.0],
+        &[52.0, 56.0, 59.0, 64.0, 68.0],
+    ];
+
+    let mut metro = Metro::new(sr);
+    metro.set_rate(1.0 / 2.5);
+
+    let mut chord: Option<&[f32]> = None;
+    let mut pos = 0;
+
+    for _ in 0 .. (sr as f32 * 12.0) as usize {
+        if metro.tick() > 0.0 {
+            if let Some(c) = chord {
+                for &note in c {
+                    bank.note_off(note);
+                }
+            }
+
+            let c = chords[pos % chords.len()];
+            for &note in c {
+                bank.note_on(note, 0.8);
+            }
+
+            chord = Some(c);
+            pos += 1;
+        }
+
+        let sum = bank.tick() * 0.15;
+        let (rvb, _) = reverb.tick(sum, sum);
+        wav.tick(sum + rvb * 0.2);
+    }
+}
@@ -1,31 +1,65 @@
+mod balloon;
 mod bigverb;
 mod butterworth;
 mod dcblocker;
 mod envelope;
 mod gesture;
 mod glot;
+mod glottal_source;
+mod klglott;
+mod midi;
 mod monowav;
 mod nose;
+mod notation;
+#[cfg(feature = "osc")]
+mod osc;
 mod phasor;
+mod phoneme;
+mod portamento;
+mod preset;
 mod rephasor;
 mod rng;
+mod rosenberg;
+mod smf;
 mod smoother;
 mod tgate;
+mod trachea;
 mod tract;
+mod tuning;
+mod twomass;
+mod vibrato;
 mod voice;
+mod voicebank;
 
+pub use balloon::*;
 pub use bigverb::*;
 pub use butterworth::*;
 pub use dcblocker::*;
 pub use envelope::*;
 pub use gesture::*;
 pub use glot::*;
+pub use glottal_source::*;
+pub use klglott::*;
+pub use midi::*;
 pub use monowav::*;
 pub use nose::*;
+pub use notation::*;
+#[cfg(feature = "osc")]
+pub use osc::*;
 pub use phasor::*;
+pub use phoneme::*;
+pub use portamento::*;
+pub use preset::*;
 pub use rephasor::*;
 pub use rng::*;
+pub use rosenberg::*;
+pub use smf::*;
 pub use smoother::*;
 pub use tgate::*;
+pub use trachea::*;
 pub use tract::*;
+pub use tuning::*;
+pub use twomass::*;
+pub use vibrato::*;
 pub use voice::*;
+pub use voicebank::*;
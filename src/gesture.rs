@@ -1,4 +1,6 @@
 use crate::RePhasor;
+use std::collections::VecDeque;
+use std::fmt;
 //use std::option;
 
 #[derive(Copy, Clone)]
@@ -13,64 +15,140 @@ pub enum Behavior {
     Zero,
     One,
     Gate,
+
+    // Parametric curves.
+    // curvature: > 0 starts slow and ends fast, < 0 the
+    // opposite, 0 is linear. Clamped to +/- 80.
+    Exponential(f32),
+    Smoothstep,
+    // cubic bezier easing, with control points (x1, y1)
+    // and (x2, y2), like CSS cubic-bezier()
+    Bezier(f32, f32, f32, f32),
+    // S curve, with a steepness (around 10 is a good start)
+    Sigmoid(f32),
+    // holds until the breakpoint (0-1), then moves with
+    // the given exponent (at least 0.01). GlissMedium is
+    // Gliss(0.75, 3.0).
+    Gliss(f32, f32),
+    // any curve, mapping 0-1 to 0-1
+    Custom(fn(f32) -> f32),
+}
+
+// Values a gesture can move between. Besides scalars, this
+// covers fixed size arrays (DRM shapes, for example) and vectors
+// (whole area functions), interpolated element by element.
+pub trait GestureValue: Clone {
+    // the value before the first vertex
+    fn zero() -> Self;
+    // a is the behavior curve output, 0 at self and 1 at next
+    fn lerp(&self, next: &Self, a: f32) -> Self;
+}
+
+impl GestureValue for f32 {
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn lerp(&self, next: &Self, a: f32) -> Self {
+        (1.0 - a) * self + a * next
+    }
+}
+
+impl<const N: usize> GestureValue for [f32; N] {
+    fn zero() -> Self {
+        [0.0; N]
+    }
+
+    fn lerp(&self, next: &Self, a: f32) -> Self {
+        let mut out = [0.0; N];
+
+        for (o, (x, y)) in out.iter_mut().zip(self.iter().zip(next.iter())) {
+            *o = x.lerp(y, a);
+        }
+
+        out
+    }
+}
+
+// The output has the length of the target. Elements the
+// previous value doesn't have start at the target. This
+// allocates every sample, so prefer arrays when the size
+// is known.
+impl GestureValue for Vec<f32> {
+    fn zero() -> Self {
+        vec![]
+    }
+
+    fn lerp(&self, next: &Self, a: f32) -> Self {
+        next.iter()
+            .enumerate()
+            .map(|(i, y)| self.get(i).unwrap_or(y).lerp(y, a))
+            .collect()
+    }
 }
 
 pub struct Gesture<T> {
     prev: T,
     next: T,
-    ratemul: T,
+    ratemul: f32,
     behavior: Behavior,
     rephasor: RePhasor,
-    lphs: T,
-    lalpha: T,
+    lphs: f32,
+    lalpha: f32,
     next_behavior: Behavior,
 }
 
-pub struct LinearGesture<'a> {
-    gest: Gesture<f32>,
-    path: Option<&'a Vec<GestureVertex<f32>>>,
+pub struct LinearGesture<'a, T = f32> {
+    gest: Gesture<T>,
+    path: Option<&'a Vec<GestureVertex<T>>>,
     pos: usize,
 }
 
-pub struct LinearGestureBuilder {
-    gest: Gesture<f32>,
-    path: Vec<GestureVertex<f32>>,
+pub struct LinearGestureBuilder<T = f32> {
+    gest: Gesture<T>,
+    path: Vec<GestureVertex<T>>,
     pos: usize,
     loopit: bool,
     alpha_only: bool,
 }
 
 #[derive(Copy, Clone)]
-enum GestureEventType {
-    None,
-    Scalar,
-    Rate,
-    Behavior,
-    Wait,
+pub enum GestureEvent {
+    Scalar(f32),
+    // num, den
+    Rate(u32, u32),
+    Behavior(Behavior),
+    // periods to hold before taking more events
+    Wait(u32),
 }
 
-#[derive(Copy, Clone)]
-// TODO: converted this form a Union because unions are unsafe.
-// There may be a more efficient way to handle this?
-struct GestureEventData {
-    rate: Option<[u32; 2]>,
-    scalar: Option<f32>,
-    behavior: Option<Behavior>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GestureEventError {
+    // the queue holds this many events already
+    QueueFull(usize),
 }
 
-#[derive(Copy, Clone)]
-pub struct GestureEvent {
-    evtype: GestureEventType,
-    data: GestureEventData,
+impl fmt::Display for GestureEventError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GestureEventError::QueueFull(capacity) => {
+                write!(f, "gesture event queue is full ({} events)", capacity)
+            }
+        }
+    }
 }
 
+impl std::error::Error for GestureEventError {}
+
+// room made up front, so that the first events don't allocate
 const EVENT_QUEUE_SIZE: usize = 16;
 
+// Grows as needed, unless it has a capacity. A fixed
+// capacity never allocates after creation, which is what
+// the audio thread wants, and rejects events when full.
 struct GestureEventQueue {
-    queue: [GestureEvent; EVENT_QUEUE_SIZE],
-    head: usize,
-    tail: usize,
-    num_events: usize,
+    queue: VecDeque<GestureEvent>,
+    capacity: Option<usize>,
 }
 
 pub struct EventfulGesture {
@@ -88,12 +166,12 @@ pub struct GestureVertex<T> {
     pub bhvr: Behavior,
 }
 
-pub trait SignalGenerator {
-    fn next_vertex(&mut self) -> GestureVertex<f32>;
+pub trait SignalGenerator<T = f32> {
+    fn next_vertex(&mut self) -> GestureVertex<T>;
     fn compute_rephasor(&mut self, clk: f32) -> f32;
-    fn interpolate(&mut self, phs: f32) -> f32;
+    fn interpolate(&mut self, phs: f32) -> T;
     fn new_period(&mut self, phs: f32) -> bool;
-    fn tick(&mut self, clk: f32) -> f32 {
+    fn tick(&mut self, clk: f32) -> T {
         let phs = self.compute_rephasor(clk);
 
         if self.new_period(phs) {
@@ -102,21 +180,21 @@ pub trait SignalGenerator {
         }
         self.interpolate(phs)
     }
-    fn update(&mut self, vtx: &GestureVertex<f32>);
+    fn update(&mut self, vtx: &GestureVertex<T>);
     fn preinit(&mut self) {
         let a = self.next_vertex();
         self.update(&a);
     }
 }
 
-impl SignalGenerator for Gesture<f32> {
+impl<T: GestureValue> SignalGenerator<T> for Gesture<T> {
     fn new_period(&mut self, phs: f32) -> bool {
         self.lphs > phs
     }
 
-    fn next_vertex(&mut self) -> GestureVertex<f32> {
+    fn next_vertex(&mut self) -> GestureVertex<T> {
         GestureVertex {
-            val: 0.0,
+            val: T::zero(),
             num: 1,
             den: 1,
             bhvr: Behavior::Linear,
@@ -127,10 +205,10 @@ impl SignalGenerator for Gesture<f32> {
         self.rephasor.tick(clk)
     }
 
-    fn interpolate(&mut self, phs: f32) -> f32 {
+    fn interpolate(&mut self, phs: f32) -> T {
         let a = apply_behavior(phs, &self.behavior);
 
-        let out = (1.0 - a) * self.prev + a * self.next;
+        let out = self.prev.lerp(&self.next, a);
 
         self.lphs = phs;
         self.lalpha = a;
@@ -138,31 +216,30 @@ impl SignalGenerator for Gesture<f32> {
         out
     }
 
-    fn update(&mut self, vtx: &GestureVertex<f32>) {
+    fn update(&mut self, vtx: &GestureVertex<T>) {
         // Set the previous rate multiplier
         // because we want this relationship: A -> A_rm (A_bhvr) -> B
         self.rephasor.set_scale(self.ratemul);
 
         // with that set, we can cache the upcoming RM
         self.ratemul = vtx.num as f32 / vtx.den as f32;
-        self.prev = self.next;
-        self.next = vtx.val;
+        self.prev = std::mem::replace(&mut self.next, vtx.val.clone());
         self.behavior = self.next_behavior;
         self.next_behavior = vtx.bhvr;
     }
 }
 
-impl Default for Gesture<f32> {
+impl<T: GestureValue> Default for Gesture<T> {
     fn default() -> Self {
-        Gesture::<f32>::new()
+        Gesture::<T>::new()
     }
 }
 
-impl Gesture<f32> {
+impl<T: GestureValue> Gesture<T> {
     pub fn new() -> Self {
         Gesture {
-            prev: 0.0,
-            next: 0.0,
+            prev: T::zero(),
+            next: T::zero(),
             ratemul: 1.0,
             behavior: Behavior::GlissMedium,
             next_behavior: Behavior::GlissMedium,
@@ -172,34 +249,93 @@ impl Gesture<f32> {
             lalpha: 0.0,
         }
     }
+
+    // the behavior curve output for the last sample
+    pub fn alpha(&self) -> f32 {
+        self.lalpha
+    }
 }
 
-fn gliss_it(phs: f32, glisspos: f32) -> f32 {
-    let mut a;
-    if phs < glisspos {
-        a = 0.0;
+// curve parameters are clamped to these, to stay finite
+const MAX_CURVATURE: f32 = 80.0;
+const MIN_EXPONENT: f32 = 0.01;
+
+fn gliss_it(phs: f32, glisspos: f32, exponent: f32) -> f32 {
+    if phs < glisspos || glisspos >= 1.0 {
+        return 0.0;
+    }
+
+    let exponent = exponent.max(MIN_EXPONENT);
+
+    let a = (phs - glisspos) / (1.0 - glisspos);
+
+    // the cubic is the common case, and cheaper than powf
+    if exponent == 3.0 {
+        a * a * a
     } else {
-        a = phs - glisspos;
-        if a < 0.0 {
-            a = 0.0;
+        a.powf(exponent)
+    }
+}
+
+fn exponential(phs: f32, curvature: f32) -> f32 {
+    if curvature.abs() < 1e-3 {
+        return phs;
+    }
+
+    // exp overflows f32 not far above this
+    let curvature = curvature.clamp(-MAX_CURVATURE, MAX_CURVATURE);
+    (curvature * phs).exp_m1() / curvature.exp_m1()
+}
+
+fn sigmoid(phs: f32, steepness: f32) -> f32 {
+    if steepness.abs() < 1e-3 {
+        return phs;
+    }
+
+    let s = |x: f32| 1.0 / (1.0 + (-steepness * (x - 0.5)).exp());
+    let lo = s(0.0);
+    (s(phs) - lo) / (s(1.0) - lo)
+}
+
+// one coordinate of a cubic bezier from (0, 0) to (1, 1)
+fn bezier_coord(t: f32, p1: f32, p2: f32) -> f32 {
+    let u = 1.0 - t;
+    3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+}
+
+fn bezier(phs: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    // x needs to be monotonic for the curve to be a function
+    let x1 = x1.clamp(0.0, 1.0);
+    let x2 = x2.clamp(0.0, 1.0);
+
+    // find t for this x by bisection
+    let mut lo = 0.0;
+    let mut hi = 1.0;
+    let mut t = phs;
+
+    for _ in 0..20 {
+        if bezier_coord(t, x1, x2) < phs {
+            lo = t;
+        } else {
+            hi = t;
         }
-        a /= 1.0 - glisspos;
-        a = a * a * a;
+        t = 0.5 * (lo + hi);
     }
-    a
+
+    bezier_coord(t, y1, y2)
 }
 
 fn apply_behavior(phs: f32, bhvr: &Behavior) -> f32 {
     match bhvr {
         Behavior::Step => 0.0,
         Behavior::Linear => phs,
-        Behavior::GlissMedium => gliss_it(phs, 0.75),
-        Behavior::GlissSmall => gliss_it(phs, 0.85),
-        Behavior::GlissLarge => gliss_it(phs, 0.5),
-        Behavior::GlissHuge => gliss_it(phs, 0.1),
-        Behavior::GlissTiny => gliss_it(phs, 0.9),
+        Behavior::GlissMedium => gliss_it(phs, 0.75, 3.0),
+        Behavior::GlissSmall => gliss_it(phs, 0.85, 3.0),
+        Behavior::GlissLarge => gliss_it(phs, 0.5, 3.0),
+        Behavior::GlissHuge => gliss_it(phs, 0.1, 3.0),
+        Behavior::GlissTiny => gliss_it(phs, 0.9, 3.0),
         Behavior::Zero => 0.0,
-        Behavior::One => 0.0,
+        Behavior::One => 1.0,
         Behavior::Gate => {
             if phs > 0.5 {
                 0.
@@ -207,16 +343,22 @@ fn apply_behavior(phs: f32, bhvr: &Behavior) -> f32 {
                 1.
             }
         }
+        Behavior::Exponential(curvature) => exponential(phs, *curvature),
+        Behavior::Smoothstep => phs * phs * (3.0 - 2.0 * phs),
+        Behavior::Bezier(x1, y1, x2, y2) => bezier(phs, *x1, *y1, *x2, *y2),
+        Behavior::Sigmoid(steepness) => sigmoid(phs, *steepness),
+        Behavior::Gliss(pos, exponent) => gliss_it(phs, *pos, *exponent),
+        Behavior::Custom(f) => f(phs),
     }
 }
 
-impl<'a> Default for LinearGesture<'a> {
+impl<'a, T: GestureValue> Default for LinearGesture<'a, T> {
     fn default() -> Self {
         LinearGesture::new()
     }
 }
 
-impl<'a> LinearGesture<'a> {
+impl<'a, T: GestureValue> LinearGesture<'a, T> {
     pub fn new() -> Self {
         LinearGesture {
             gest: Gesture::new(),
@@ -225,7 +367,7 @@ impl<'a> LinearGesture<'a> {
         }
     }
 
-    pub fn init(&mut self, path: &'a Vec<GestureVertex<f32>>) {
+    pub fn init(&mut self, path: &'a Vec<GestureVertex<T>>) {
         self.path = Some(path);
         // get vertex, now next vertex is on deck
         let a = self.next_vertex();
@@ -239,11 +381,11 @@ impl<'a> LinearGesture<'a> {
     }
 }
 
-impl SignalGenerator for LinearGesture<'_> {
-    fn next_vertex(&mut self) -> GestureVertex<f32> {
+impl<T: GestureValue> SignalGenerator<T> for LinearGesture<'_, T> {
+    fn next_vertex(&mut self) -> GestureVertex<T> {
         match self.path {
             Some(x) => {
-                let nxt = x[self.pos];
+                let nxt = x[self.pos].clone();
                 self.pos += 1;
                 if self.pos >= x.len() {
                     self.pos = 0;
@@ -252,7 +394,7 @@ impl SignalGenerator for LinearGesture<'_> {
             }
 
             None => GestureVertex {
-                val: 0.0,
+                val: T::zero(),
                 num: 1,
                 den: 1,
                 bhvr: Behavior::Linear,
@@ -264,7 +406,7 @@ impl SignalGenerator for LinearGesture<'_> {
         self.gest.compute_rephasor(clk)
     }
 
-    fn interpolate(&mut self, phs: f32) -> f32 {
+    fn interpolate(&mut self, phs: f32) -> T {
         self.gest.interpolate(phs)
     }
 
@@ -272,18 +414,18 @@ impl SignalGenerator for LinearGesture<'_> {
         self.gest.new_period(phs)
     }
 
-    fn update(&mut self, vtx: &GestureVertex<f32>) {
+    fn update(&mut self, vtx: &GestureVertex<T>) {
         self.gest.update(vtx);
     }
 }
 
-impl Default for LinearGestureBuilder {
+impl<T: GestureValue> Default for LinearGestureBuilder<T> {
     fn default() -> Self {
         LinearGestureBuilder::new()
     }
 }
 
-impl LinearGestureBuilder {
+impl<T: GestureValue> LinearGestureBuilder<T> {
     pub fn new() -> Self {
         LinearGestureBuilder {
             gest: Gesture::new(),
@@ -295,7 +437,7 @@ impl LinearGestureBuilder {
     }
 
     // Appends vertex to the path
-    pub fn append(&mut self, vtx: GestureVertex<f32>) {
+    pub fn append(&mut self, vtx: GestureVertex<T>) {
         self.path.push(vtx);
     }
 
@@ -306,12 +448,33 @@ impl LinearGestureBuilder {
             self.update(&a);
         }
     }
+
+    // loop back to the start at the end of the path,
+    // instead of holding the last value
+    pub fn set_loop(&mut self, loopit: bool) {
+        self.loopit = loopit;
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.gest.alpha()
+    }
 }
 
-impl SignalGenerator for LinearGestureBuilder {
-    fn next_vertex(&mut self) -> GestureVertex<f32> {
+impl<T: GestureValue> SignalGenerator<T> for LinearGestureBuilder<T> {
+    fn next_vertex(&mut self) -> GestureVertex<T> {
         let x = &self.path;
-        let nxt = x[self.pos];
+
+        // nothing appended: hold still
+        if x.is_empty() {
+            return GestureVertex {
+                val: T::zero(),
+                num: 1,
+                den: 1,
+                bhvr: Behavior::Linear,
+            };
+        }
+
+        let nxt = x[self.pos].clone();
         self.pos += 1;
         if self.pos >= x.len() {
             // just hold at the end, don't loop back
@@ -328,7 +491,7 @@ impl SignalGenerator for LinearGestureBuilder {
         self.gest.compute_rephasor(clk)
     }
 
-    fn interpolate(&mut self, phs: f32) -> f32 {
+    fn interpolate(&mut self, phs: f32) -> T {
         self.gest.interpolate(phs)
     }
 
@@ -336,34 +499,9 @@ impl SignalGenerator for LinearGestureBuilder {
         self.gest.new_period(phs)
     }
 
-    fn update(&mut self, vtx: &GestureVertex<f32>) {
+    fn update(&mut self, vtx: &GestureVertex<T>) {
         self.gest.update(vtx);
     }
-
-    // fn tick(&mut self, clk: f32) -> f32 {
-    //     let out = self.gest.tick(clk);
-
-    //     if self.alpha_only {
-    //         return self.gest.lalpha;
-    //     }
-
-    //     out
-    // }
-    fn tick(&mut self, clk: f32) -> f32 {
-        let phs = self.compute_rephasor(clk);
-
-        if self.new_period(phs) {
-            let vtx = self.next_vertex();
-            self.update(&vtx);
-        }
-        let out = self.interpolate(phs);
-
-        if self.alpha_only {
-            return self.gest.lalpha;
-        }
-
-        out
-    }
 }
 
 pub fn behavior_from_integer(bhvr: u16) -> Result<Behavior, u16> {
@@ -382,9 +520,30 @@ pub fn behavior_from_integer(bhvr: u16) -> Result<Behavior, u16> {
     }
 }
 
+// Like behavior_from_integer, with the parametric curves
+// after it. Unused parameters are ignored.
+// 10: Exponential(p1), 11: Smoothstep, 12: Bezier(p1, p2, p3, p4),
+// 13: Sigmoid(p1), 14: Gliss(p1, p2)
+pub fn behavior_from_params(bhvr: u16, p: [f32; 4]) -> Result<Behavior, u16> {
+    match bhvr {
+        10 => Ok(Behavior::Exponential(p[0])),
+        11 => Ok(Behavior::Smoothstep),
+        12 => Ok(Behavior::Bezier(p[0], p[1], p[2], p[3])),
+        13 => Ok(Behavior::Sigmoid(p[0])),
+        14 => Ok(Behavior::Gliss(p[0], p[1])),
+        _ => behavior_from_integer(bhvr),
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn vb_gesture_tick(vb: &mut LinearGestureBuilder, clk: f32) -> f32 {
-    vb.tick(clk)
+    let out = vb.tick(clk);
+
+    if vb.alpha_only {
+        return vb.alpha();
+    }
+
+    out
 }
 
 #[no_mangle]
@@ -419,6 +578,29 @@ pub extern "C" fn vb_gesture_append(
     }
 }
 
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn vb_gesture_append_param(
+    vb: &mut LinearGestureBuilder,
+    val: f32,
+    num: u32,
+    den: u32,
+    bhvr: u16,
+    p1: f32,
+    p2: f32,
+    p3: f32,
+    p4: f32,
+) {
+    if let Ok(bhvr) = behavior_from_params(bhvr, [p1, p2, p3, p4]) {
+        vb.append(GestureVertex {
+            val,
+            num,
+            den,
+            bhvr,
+        });
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn vb_gesture_new() -> Box<LinearGestureBuilder> {
     Box::new(LinearGestureBuilder::new())
@@ -436,30 +618,20 @@ pub extern "C" fn vb_gesture_alpha(vb: &mut LinearGestureBuilder) {
 
 impl SignalGenerator for EventfulGesture {
     fn next_vertex(&mut self) -> GestureVertex<f32> {
-        let events = &mut self.events;
-        while events.has_events() && self.wait == 0 {
-            let evt = events.dequeue();
-
-            match evt.evtype {
-                GestureEventType::Scalar => {
-                    self.vtx.val = evt.data.scalar.expect("no scalar found");
-                }
-                GestureEventType::Behavior => {
-                    self.vtx.bhvr = evt.data.behavior.expect("no behavior found");
-                }
-                GestureEventType::Rate => {
-                    let rate = evt.data.rate.expect("no rate found");
-                    self.vtx.num = rate[0];
-                    self.vtx.den = rate[1];
-                }
-                GestureEventType::Wait => {
-                    // HACK: use rate to store data
-                    let wait = evt.data.rate.expect("no wait found");
-                    let wait = wait[0];
-
-                    self.wait = wait;
+        while self.wait == 0 {
+            let evt = match self.events.dequeue() {
+                Some(evt) => evt,
+                None => break,
+            };
+
+            match evt {
+                GestureEvent::Scalar(val) => self.vtx.val = val,
+                GestureEvent::Behavior(bhvr) => self.vtx.bhvr = bhvr,
+                GestureEvent::Rate(num, den) => {
+                    self.vtx.num = num;
+                    self.vtx.den = den;
                 }
-                _ => {}
+                GestureEvent::Wait(wait) => self.wait = wait,
             }
         }
 
@@ -488,9 +660,25 @@ impl SignalGenerator for EventfulGesture {
 
 impl Default for EventfulGesture {
     fn default() -> Self {
+        EventfulGesture::new()
+    }
+}
+
+impl EventfulGesture {
+    // with a queue that grows as needed
+    pub fn new() -> Self {
+        EventfulGesture::with_queue(GestureEventQueue::new())
+    }
+
+    // with a queue that holds at most this many events
+    pub fn with_capacity(capacity: usize) -> Self {
+        EventfulGesture::with_queue(GestureEventQueue::with_capacity(capacity))
+    }
+
+    fn with_queue(events: GestureEventQueue) -> Self {
         EventfulGesture {
             gest: Gesture::new(),
-            events: GestureEventQueue::new(),
+            events,
             vtx: GestureVertex {
                 val: 0.,
                 num: 1,
@@ -500,20 +688,30 @@ impl Default for EventfulGesture {
             wait: 0,
         }
     }
-}
 
-impl EventfulGesture {
-    pub fn scalar(&mut self, scalar: f32) {
-        self.events.enqueue_scalar(scalar);
+    pub fn event(&mut self, evt: GestureEvent) -> Result<(), GestureEventError> {
+        self.events.enqueue(evt)
     }
-    pub fn rate(&mut self, rate: [u32; 2]) {
-        self.events.enqueue_rate(rate[0], rate[1]);
+    pub fn scalar(&mut self, scalar: f32) -> Result<(), GestureEventError> {
+        self.event(GestureEvent::Scalar(scalar))
     }
-    pub fn behavior(&mut self, bhvr: Behavior) {
-        self.events.enqueue_behavior(bhvr);
+    pub fn rate(&mut self, rate: [u32; 2]) -> Result<(), GestureEventError> {
+        self.event(GestureEvent::Rate(rate[0], rate[1]))
     }
-    pub fn wait(&mut self, wait: u32) {
-        self.events.enqueue_wait(wait);
+    pub fn behavior(&mut self, bhvr: Behavior) -> Result<(), GestureEventError> {
+        self.event(GestureEvent::Behavior(bhvr))
+    }
+    pub fn wait(&mut self, wait: u32) -> Result<(), GestureEventError> {
+        self.event(GestureEvent::Wait(wait))
+    }
+
+    pub fn pending(&self) -> usize {
+        self.events.len()
+    }
+
+    // room left in the event queue, None if it grows as needed
+    pub fn remaining(&self) -> Option<usize> {
+        self.events.remaining()
     }
 
     pub fn immediate(&mut self, value: f32) {
@@ -530,146 +728,242 @@ impl EventfulGesture {
 
 impl GestureEventQueue {
     pub fn new() -> Self {
-        let evt_default = GestureEvent {
-            evtype: GestureEventType::None,
-            data: GestureEventData {
-                scalar: None,
-                rate: None,
-                behavior: None,
-            },
-        };
         GestureEventQueue {
-            queue: [evt_default; EVENT_QUEUE_SIZE],
-            head: 0,
-            tail: 0,
-            num_events: 0,
+            queue: VecDeque::with_capacity(EVENT_QUEUE_SIZE),
+            capacity: None,
         }
     }
 
-    pub fn enqueue_scalar(&mut self, scalar: f32) {
-        let evt = self.enqueue();
+    pub fn with_capacity(capacity: usize) -> Self {
+        GestureEventQueue {
+            queue: VecDeque::with_capacity(capacity),
+            capacity: Some(capacity),
+        }
+    }
+
+    pub fn enqueue(&mut self, evt: GestureEvent) -> Result<(), GestureEventError> {
+        if let Some(capacity) = self.capacity {
+            if self.queue.len() >= capacity {
+                return Err(GestureEventError::QueueFull(capacity));
+            }
+        }
 
-        evt.evtype = GestureEventType::Scalar;
-        evt.data.scalar = Some(scalar);
-        evt.data.behavior = None;
-        evt.data.rate = None;
+        self.queue.push_back(evt);
+        Ok(())
     }
 
-    pub fn enqueue_rate(&mut self, num: u32, den: u32) {
-        let evt = self.enqueue();
+    pub fn dequeue(&mut self) -> Option<GestureEvent> {
+        self.queue.pop_front()
+    }
 
-        evt.evtype = GestureEventType::Rate;
-        evt.data.scalar = None;
-        evt.data.behavior = None;
-        evt.data.rate = Some([num, den]);
+    pub fn len(&self) -> usize {
+        self.queue.len()
     }
 
-    pub fn enqueue_behavior(&mut self, bhvr: Behavior) {
-        let evt = self.enqueue();
+    // room left for events, None if the queue grows as needed
+    pub fn remaining(&self) -> Option<usize> {
+        self.capacity.map(|c| c.saturating_sub(self.queue.len()))
+    }
 
-        evt.evtype = GestureEventType::Behavior;
-        evt.data.scalar = None;
-        evt.data.behavior = Some(bhvr);
-        evt.data.rate = None;
+    pub fn clear_events(&mut self) {
+        self.queue.clear();
     }
+}
 
-    pub fn enqueue_wait(&mut self, wait: u32) {
-        let evt = self.enqueue();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        evt.evtype = GestureEventType::Wait;
-        evt.data.scalar = None;
-        // HACK: re-use rate to store wait value
-        evt.data.rate = Some([wait, 0]);
+    #[test]
+    fn test_behavior_from_integer() {
+        // Out of bounds error
+        let result = behavior_from_integer(9999).is_err();
+        assert!(result);
     }
 
-    /// Enques event and returns reference to it
-    pub fn enqueue(&mut self) -> &mut GestureEvent {
-        if self.num_events >= EVENT_QUEUE_SIZE {
-            panic!("Event overflow")
+    #[test]
+    fn test_behavior_curves() {
+        fn square(x: f32) -> f32 {
+            x * x
         }
-        let evt = &mut self.queue[self.tail];
 
-        // initialize values to be nothing
+        let curves = [
+            Behavior::Linear,
+            Behavior::GlissMedium,
+            Behavior::Exponential(4.0),
+            Behavior::Exponential(-4.0),
+            Behavior::Exponential(0.0),
+            Behavior::Exponential(100.0),
+            Behavior::Exponential(-1000.0),
+            Behavior::Smoothstep,
+            Behavior::Bezier(0.25, 0.1, 0.25, 1.0),
+            Behavior::Sigmoid(10.0),
+            Behavior::Gliss(0.3, 2.0),
+            Behavior::Gliss(0.5, -1.0),
+            Behavior::Gliss(0.5, 0.0),
+            Behavior::Sigmoid(1000.0),
+            Behavior::Custom(square),
+        ];
+
+        for b in curves.iter() {
+            assert!(apply_behavior(0.0, b).abs() < 1e-5);
+            assert!((apply_behavior(1.0, b) - 1.0).abs() < 1e-5);
+
+            let mut prev = 0.0;
+            for i in 0..=100 {
+                let y = apply_behavior(i as f32 / 100.0, b);
+                assert!(y.is_finite());
+                assert!(y >= prev - 1e-5);
+                prev = y;
+            }
+        }
 
-        evt.evtype = GestureEventType::None;
-        evt.data.scalar = None;
-        evt.data.behavior = None;
-        evt.data.behavior = None;
-        evt.data.rate = None;
+        assert_eq!(apply_behavior(0.5, &Behavior::One), 1.0);
+        assert_eq!(apply_behavior(0.5, &Behavior::Zero), 0.0);
 
-        self.tail += 1;
-        self.tail %= EVENT_QUEUE_SIZE;
-        self.num_events += 1;
+        // gliss generalizes the fixed ones
+        for i in 0..=10 {
+            let x = i as f32 / 10.0;
+            let a = apply_behavior(x, &Behavior::GlissLarge);
+            let b = apply_behavior(x, &Behavior::Gliss(0.5, 3.0));
+            assert_eq!(a, b);
+        }
 
-        evt
+        // linear-ish bezier
+        let y = apply_behavior(0.3, &Behavior::Bezier(0.3, 0.3, 0.7, 0.7));
+        assert!((y - 0.3).abs() < 1e-3);
+
+        assert!(apply_behavior(0.2, &Behavior::Exponential(4.0)) < 0.2);
+        assert!(apply_behavior(0.2, &Behavior::Exponential(-4.0)) > 0.2);
+
+        assert!(matches!(
+            behavior_from_params(14, [0.5, 2.0, 0.0, 0.0]),
+            Ok(Behavior::Gliss(_, _))
+        ));
+        assert!(matches!(
+            behavior_from_params(4, [0.0; 4]),
+            Ok(Behavior::GlissMedium)
+        ));
+        assert!(behavior_from_params(15, [0.0; 4]).is_err());
     }
 
-    pub fn dequeue(&mut self) -> &GestureEvent {
-        if self.num_events == 0 {
-            panic!("event underflow")
+    #[test]
+    fn test_vector_gesture() {
+        let vals = [60.0, 67.0, 62.0, 72.0];
+        let bhvrs = [
+            Behavior::GlissMedium,
+            Behavior::Linear,
+            Behavior::Smoothstep,
+            Behavior::Step,
+        ];
+
+        let mut scalar = LinearGestureBuilder::new();
+        let mut array = LinearGestureBuilder::<[f32; 2]>::new();
+        let mut vector = LinearGestureBuilder::<Vec<f32>>::new();
+
+        for (i, (val, bhvr)) in vals.iter().zip(bhvrs.iter()).enumerate() {
+            let (num, den) = (1 + i as u32 % 2, 2);
+            scalar.append(GestureVertex {
+                val: *val,
+                num,
+                den,
+                bhvr: *bhvr,
+            });
+            array.append(GestureVertex {
+                val: [*val, -val],
+                num,
+                den,
+                bhvr: *bhvr,
+            });
+            // the last vertex has one element less
+            vector.append(GestureVertex {
+                val: vec![*val, 2.0 * val][..2 - i / 3].to_vec(),
+                num,
+                den,
+                bhvr: *bhvr,
+            });
         }
 
-        let evt = &self.queue[self.head];
-
-        self.head += 1;
-        self.head %= EVENT_QUEUE_SIZE;
-        self.num_events -= 1;
+        scalar.done();
+        array.done();
+        vector.done();
 
-        evt
-    }
-
-    pub fn has_events(&self) -> bool {
-        self.num_events > 0
-    }
+        let mut clk = crate::Phasor::new(1000, 0.0);
+        clk.set_freq(10.0);
 
-    pub fn clear_events(&mut self) {
-        self.num_events = 0;
-        self.head = 0;
-        self.tail = 0;
-    }
-}
+        for n in 0..1000 {
+            let c = clk.tick();
+            let x = scalar.tick(c);
+            let a = array.tick(c);
+            let v = vector.tick(c);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+            // all of them stay in sync, with the same curves
+            assert_eq!(a[0], x, "sample {}", n);
+            assert_eq!(a[1], -x, "sample {}", n);
+            assert_eq!(v[0], x, "sample {}", n);
+            assert_eq!(scalar.alpha(), array.alpha());
+        }
 
-    #[test]
-    fn test_behavior_from_integer() {
-        // Out of bounds error
-        let result = behavior_from_integer(9999).is_err();
-        assert!(result);
+        // the last vertex decides the length
+        let v = vector.tick(clk.tick());
+        assert_eq!(v, vec![72.0]);
     }
 
     #[test]
     fn test_event_queue() {
         let mut queue = GestureEventQueue::new();
-        queue.enqueue_scalar(123.0);
-        queue.enqueue_scalar(456.0);
-        assert_eq!(queue.num_events, 2);
-
-        let evt1 = queue.dequeue();
+        queue.enqueue(GestureEvent::Scalar(123.0)).unwrap();
+        queue.enqueue(GestureEvent::Scalar(456.0)).unwrap();
+        assert_eq!(queue.len(), 2);
 
-        let result = matches!(evt1.evtype, GestureEventType::Scalar);
-        assert!(result);
+        assert!(matches!(queue.dequeue(), Some(GestureEvent::Scalar(x)) if x == 123.0));
+        assert_eq!(queue.len(), 1);
+        assert!(matches!(queue.dequeue(), Some(GestureEvent::Scalar(x)) if x == 456.0));
+        assert_eq!(queue.len(), 0);
 
-        assert!(evt1.data.scalar.is_some());
+        // empty, but no panic
+        assert!(queue.dequeue().is_none());
 
-        if let Some(x) = evt1.data.scalar {
-            assert_eq!(x, 123.0);
+        // grows past the initial size
+        for i in 0..EVENT_QUEUE_SIZE * 4 {
+            queue.enqueue(GestureEvent::Wait(i as u32)).unwrap();
         }
+        assert_eq!(queue.len(), EVENT_QUEUE_SIZE * 4);
 
-        assert_eq!(queue.num_events, 1);
-        let evt2 = queue.dequeue();
-        assert!(evt2.data.scalar.is_some());
+        // a bounded queue rejects events when full, and
+        // takes them again once there is room
+        let mut queue = GestureEventQueue::with_capacity(2);
+        queue.enqueue(GestureEvent::Rate(1, 2)).unwrap();
+        queue.enqueue(GestureEvent::Behavior(Behavior::Step)).unwrap();
+        assert_eq!(
+            queue.enqueue(GestureEvent::Wait(1)),
+            Err(GestureEventError::QueueFull(2))
+        );
+        assert_eq!(queue.remaining(), Some(0));
+        assert!(matches!(queue.dequeue(), Some(GestureEvent::Rate(1, 2))));
+        assert_eq!(queue.remaining(), Some(1));
+        assert!(queue.enqueue(GestureEvent::Wait(1)).is_ok());
+        assert_eq!(queue.len(), 2);
+        assert_eq!(GestureEventQueue::new().remaining(), None);
+    }
 
-        let result = matches!(evt2.evtype, GestureEventType::Scalar);
-        assert!(result);
+    #[test]
+    fn test_bounded_eventful_gesture() {
+        let mut evtgst = EventfulGesture::with_capacity(4);
 
-        if let Some(x) = evt2.data.scalar {
-            assert_eq!(x, 456.0);
-        }
+        // a burst of events fills the queue without panicking
+        let results: Vec<_> = (0..10).map(|i| evtgst.scalar(60. + i as f32)).collect();
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 4);
+        assert_eq!(evtgst.pending(), 4);
 
-        assert_eq!(queue.num_events, 0);
+        // the accepted events still play, and ticking an
+        // empty queue holds the last value
+        evtgst.preinit();
+        for _ in 0..100 {
+            evtgst.tick(0.5);
+        }
+        assert_eq!(evtgst.pending(), 0);
+        assert_eq!(evtgst.vtx.val, 63.0);
     }
 
     #[test]
@@ -677,9 +971,9 @@ mod tests {
         let mut evtgst = EventfulGesture::default();
         let mut phs = 0.;
         let inc = 0.1;
-        evtgst.scalar(60.);
-        evtgst.rate([2, 3]);
-        evtgst.behavior(Behavior::Linear);
+        evtgst.scalar(60.).unwrap();
+        evtgst.rate([2, 3]).unwrap();
+        evtgst.behavior(Behavior::Linear).unwrap();
 
         evtgst.preinit();
         let x = evtgst.tick(phs);
@@ -700,7 +994,7 @@ mod tests {
 
         phs += inc;
 
-        evtgst.scalar(65.);
+        evtgst.scalar(65.).unwrap();
 
         evtgst.tick(phs);
         phs += inc;
@@ -745,9 +1039,9 @@ mod tests {
         let mut phs = 0.;
         let inc = 0.1;
 
-        evtgst.scalar(60.);
-        evtgst.rate([1, 1]);
-        evtgst.behavior(Behavior::Linear);
+        evtgst.scalar(60.).unwrap();
+        evtgst.rate([1, 1]).unwrap();
+        evtgst.behavior(Behavior::Linear).unwrap();
 
         evtgst.preinit();
 
@@ -772,8 +1066,8 @@ mod tests {
             count += 1;
 
             if count == 1 {
-                evtgst.wait(1);
-                evtgst.scalar(65.);
+                evtgst.wait(1).unwrap();
+                evtgst.scalar(65.).unwrap();
             }
 
             assert!(count < 20, "probably an unbounded loop");
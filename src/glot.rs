@@ -1,15 +1,36 @@
 const GLOT_ENV_SIZE: usize = 512;
 const LCG_MAX: u32 = 2147483648;
+
+// Subglottal pressures (in Pascals) used by the vocal effort
+// mapping. Effort 0 sits at the phonation threshold, effort 1
+// is a loud, pressed voice. Normal speech is around 800Pa.
+const PRESSURE_MIN: f32 = 300.0;
+const PRESSURE_NOMINAL: f32 = 800.0;
+const PRESSURE_MAX: f32 = 2400.0;
+
+// Voicing stops when the pressure drops below the offset
+// threshold, and only starts again above the onset threshold
+// (PRESSURE_MIN): phonation is easier to keep going than to
+// start. The voicing gain is smoothed over VOICING_TIME seconds.
+const PRESSURE_OFFSET: f32 = 200.0;
+const VOICING_TIME: f32 = 0.015;
+
+// pulse rates that vocal fry and creak drift towards
+const FRY_FREQ: f32 = 45.0;
+const CREAK_FREQ: f32 = 70.0;
+
+// Source-tract coupling: leak of the integrated tract pressure,
+// and a scale that makes coupling = 1 a strong interaction
+const LOAD_LEAK: f32 = 0.995;
+const LOAD_SCALE: f32 = 4.0;
 use std::f32::consts::PI;
 use crate::butterworth::{ButterworthLowPass, ButterworthHighPass};
+use crate::LinearCongruentialGenerator;
+use crate::Tuning;
 
-// midi-to-frequency converter
-fn mtof(nn: f32) -> f32 {
-    let freq = (2.0_f32).powf((nn - 69.0) / 12.0) * 440.0;
-    freq
-}
-
-const JI_RATIOS: [f32; 8] = [
+// just intonation major scale, used by set_pitch_ji.
+// Steps past the octave wrap around into higher octaves.
+const JI_RATIOS: [f32; 7] = [
     /* do */
     1.00000,
 
@@ -30,15 +51,50 @@ const JI_RATIOS: [f32; 8] = [
 
     /* 15:8 (ti) */
     1.87500,
-
-    /* 2:1 (do) */
-    2.00000
 ];
 
+#[derive(Copy, Clone)]
+pub enum GlotQuality {
+    // evaluates the waveform directly, aliases at high pitches
+    Naive,
+
+    // polyBLAMP corrections at the waveform corners,
+    // adds one sample of latency
+    PolyBlamp,
+}
+
+// All the signals produced by Glot for one sample,
+// see tick_multi.
+#[derive(Copy, Clone, Default)]
+pub struct GlotOutput {
+    // glottal flow. This is the integral of the flow
+    // derivative over normalized (per-period) time.
+    pub flow: f32,
+
+    // glottal flow derivative, without noise
+    pub flow_derivative: f32,
+
+    // aspiration (or whisper) noise component
+    pub noise: f32,
+
+    // position in the current period, 0-1
+    pub phase: f32,
+
+    // true on the sample where the glottal closure
+    // instant (Te) was crossed
+    pub gci: bool,
+
+    // true on the first sample of a new period
+    pub period_start: bool,
+}
+
 pub struct Glot {
     freq: f32,
     r_d: f32,
     waveform_length: f32,
+    // length of the glottal pulse itself. Same as the
+    // waveform length, unless phonation modes add a closed phase.
+    pulse_length: f32,
     time_in_waveform: f32,
 
     /* Pulsed Noise */
@@ -70,6 +126,84 @@ pub struct Glot {
 
     asp_hpfilt: ButterworthHighPass,
     asp_lpfilt: ButterworthLowPass,
+    asp_hp_freq: f32,
+    asp_lp_freq: f32,
+
+    /* whisper (unvoiced excitation) */
+    whisper: f32,
+    pwhisper: f32,
+    whisper_level: f32,
+    whisper_hp_freq: f32,
+    whisper_lp_freq: f32,
+
+    /* cycle-to-cycle perturbations */
+    jitter: f32, // percent of period
+    shimmer: f32, // percent of amplitude
+    period_gain: f32,
+    amplitude: f32,
+    perturb_rng: LinearCongruentialGenerator,
+
+    quality: GlotQuality,
+    blamp_prev: f32,
+    // flow, phase and GCI delayed to line up with
+    // the (delayed) polyBLAMP output
+    blamp_delayed: GlotOutput,
+
+    // flow at Te, where the return phase starts
+    flow_te: f32,
+
+    // peak flow (at Tp) of the current pulse, and the
+    // normalized glottal opening from the last tick
+    flow_peak: f32,
+    opening: f32,
+
+    // last values of the period start and closure flags,
+    // see period_trigger and closure_trigger
+    last_output: GlotOutput,
+
+    /* phonation modes, 0-1 */
+    creak: f32,
+    fry: f32,
+    diplophonia: f32,
+    period_count: u32,
+
+    tuning: Tuning,
+
+    // source-tract interaction, see set_coupling
+    coupling: f32,
+    load_gain: f32,
+    load_pressure: f32,
+
+    // pressure driven voicing (see set_subglottal_pressure):
+    // voiced state, smoothed voicing gain, and the breath
+    // noise gain, which follows the airflow
+    voiced: bool,
+    voicing: f32,
+    voicing_coef: f32,
+    breath: f32,
+
+    // how the pressure scales amplitude, Rd, aspiration and
+    // noise floor. All 1 at the nominal pressure, so the values
+    // set directly are what a normal voice sounds like.
+    pressure_gain: f32,
+    rd_scale: f32,
+    aspiration_scale: f32,
+    noise_scale: f32,
+}
+
+// Rd, aspiration and noise floor along the effort curves,
+// for a subglottal pressure in Pascals
+fn effort_quality(pressure: f32) -> (f32, f32, f32) {
+    let effort =
+        (pressure / PRESSURE_MIN).max(1.0).ln() /
+        (PRESSURE_MAX / PRESSURE_MIN).ln();
+    let lax = 1.0 - effort.min(1.0);
+
+    (
+        0.6 + 2.1 * lax.powf(1.6),
+        0.05 + 0.25 * lax * lax,
+        0.1 + 0.5 * lax * lax,
+    )
 }
 
 impl Glot {
@@ -95,9 +229,45 @@ impl Glot {
             rng: 0,
             t_env_start: 0.0,
             waveform_length: 0.0,
+            pulse_length: 0.0,
             hanning: [0.0; GLOT_ENV_SIZE],
             asp_lpfilt: ButterworthLowPass::new(sr),
             asp_hpfilt: ButterworthHighPass::new(sr),
+            asp_hp_freq: 4500.0,
+            asp_lp_freq: 6000.0,
+            whisper: 0.0,
+            pwhisper: 0.0,
+            whisper_level: 1.0,
+            whisper_hp_freq: 600.0,
+            whisper_lp_freq: 8000.0,
+            jitter: 0.0,
+            shimmer: 0.0,
+            period_gain: 1.0,
+            amplitude: 1.0,
+            perturb_rng: LinearCongruentialGenerator::new(),
+            quality: GlotQuality::Naive,
+            blamp_prev: 0.0,
+            blamp_delayed: GlotOutput::default(),
+            flow_te: 0.0,
+            flow_peak: 1.0,
+            opening: 0.0,
+            last_output: GlotOutput::default(),
+            creak: 0.0,
+            fry: 0.0,
+            diplophonia: 0.0,
+            period_count: 0,
+            tuning: Tuning::default(),
+            coupling: 0.0,
+            load_gain: 1.0,
+            load_pressure: 0.0,
+            voiced: true,
+            voicing: 1.0,
+            voicing_coef: 1.0 - (-1.0 / (VOICING_TIME * sr as f32)).exp(),
+            breath: 1.0,
+            pressure_gain: 1.0,
+            rd_scale: 1.0,
+            aspiration_scale: 1.0,
+            noise_scale: 1.0,
         };
 
         glt.set_aspiration(0.5);
@@ -105,13 +275,16 @@ impl Glot {
         glt.srand(0);
         glt.setup_waveform();
         glt.setup_hanning_table();
-        glt.asp_lpfilt.set_freq(6000.0);
-        glt.asp_hpfilt.set_freq(4500.0);
+        glt.update_noise_filters();
         glt
     }
 
     fn setup_waveform(&mut self) {
-        let mut r_d = self.r_d;
+        let mut r_d = self.r_d * self.rd_scale;
+
+        // creak and fry are produced with tight, pressed folds
+        let pressed = self.creak.max(self.fry);
+        r_d += (0.5 - r_d) * pressed;
 
         if r_d < 0.5 {
             r_d = 0.5;
@@ -121,7 +294,15 @@ impl Glot {
             r_d = 2.7;
         }
 
-        self.waveform_length = 1.0 / self.freq;
+        // jitter and shimmer: new random deviations are drawn
+        // once per period, so they are pitch-synchronous
+        let jit = self.perturbation() * self.jitter * 0.01;
+        let modal = (1.0 / self.freq) * (1.0 + jit);
+
+        let shim = self.perturbation() * self.shimmer * 0.01;
+        self.period_gain = 1.0 + shim;
+
+        self.setup_phonation_mode(modal);
 
         let r_a = -0.01 + 0.048*r_d;
         let r_k = 0.224 + 0.118*r_d;
@@ -158,6 +339,8 @@ impl Glot {
         self.delta = delta;
         self.t_e = t_e;
         self.omega = omega;
+        self.flow_te = self.flow_at(t_e);
+        self.flow_peak = self.flow_at(t_p).max(1e-6);
 
         // calculate envelope start from lag
         // and glottal closure (Te) (note that Te is normalized)
@@ -175,6 +358,53 @@ impl Glot {
         self.env_delta = 1.0 / (GLOT_ENV_SIZE as f32 * self.env_size);
     }
 
+    // Works out the period and pulse length (and gain) for
+    // the upcoming period, based on the phonation modes.
+    fn setup_phonation_mode(&mut self, modal: f32) {
+        let mut period = modal;
+        let mut pulse = modal;
+
+        // Vocal fry: regular pulses at a very low rate. The pulses
+        // keep (roughly) their modal shape, so they are followed
+        // by a long closed phase.
+        if self.fry > 0.0 {
+            let fry_period = 1.0 / FRY_FREQ;
+            if fry_period > period {
+                period *= (fry_period / period).powf(self.fry);
+            }
+            pulse *= 1.0 - 0.5 * self.fry;
+        }
+
+        // Creak: like fry, but with strongly irregular
+        // pulse timing and amplitudes
+        if self.creak > 0.0 {
+            let creak_period = 1.0 / CREAK_FREQ;
+            if creak_period > period {
+                period *= (creak_period / period).powf(self.creak);
+            }
+            period *= 1.0 + 0.3 * self.creak * self.perturbation();
+            pulse *= 1.0 - 0.4 * self.creak;
+            self.period_gain *= 1.0 + 0.4 * self.creak * self.perturbation();
+        }
+
+        // Diplophonia (period doubling): every other pulse is
+        // longer and weaker than its neighbours
+        if self.diplophonia > 0.0 {
+            let odd = self.period_count % 2 == 1;
+            let stretch = if odd { 0.15 } else { -0.15 };
+            period *= 1.0 + stretch * self.diplophonia;
+            pulse *= 1.0 + stretch * self.diplophonia;
+
+            if odd {
+                self.period_gain *= 1.0 - 0.5 * self.diplophonia;
+            }
+        }
+
+        self.period_count = self.period_count.wrapping_add(1);
+        self.waveform_length = period;
+        self.pulse_length = pulse.min(period);
+    }
+
     fn setup_hanning_table(&mut self) {
         let om = 2.0 * PI / (GLOT_ENV_SIZE as f32);
 
@@ -189,22 +419,185 @@ impl Glot {
         self.freq = freq;
     }
 
+    // Pitch is a note number, resolved through the tuning.
+    // Unmapped notes leave the frequency unchanged.
     pub fn set_pitch(&mut self, pitch: f32) {
-        self.set_freq(mtof(pitch));
+        if let Some(freq) = self.tuning.note_to_freq(pitch) {
+            self.set_freq(freq);
+        }
     }
 
     pub fn set_pitch_ji(&mut self, base: f32, step: usize) {
-        self.set_freq(mtof(base) * JI_RATIOS[step]);
+        let octave = (step / JI_RATIOS.len()) as i32;
+        let ratio = JI_RATIOS[step % JI_RATIOS.len()] * (2.0_f32).powi(octave);
+
+        if let Some(freq) = self.tuning.note_to_freq(base) {
+            self.set_freq(freq * ratio);
+        }
+    }
+
+    // sets the pitch to a degree of the tuning's scale,
+    // counted from its middle note, plus octaves (periods)
+    pub fn set_pitch_degree(&mut self, degree: i32, octave: i32) {
+        let freq = self.tuning.degree_freq(degree, octave);
+        self.set_freq(freq);
+    }
+
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
     }
 
     pub fn set_shape(&mut self, shape: f32) {
         self.r_d = 3.0 * (1.0 - shape);
     }
 
+    // sets the LF shape parameter directly. Useful range is
+    // 0.5 (tense, pressed) to 2.7 (lax, breathy).
+    pub fn set_rd(&mut self, r_d: f32) {
+        self.r_d = r_d;
+    }
+
+    // amplitude of the voiced (pulse) component
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude;
+    }
+
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    // gain of the voiced component for the current period
+    fn gain(&self) -> f32 {
+        self.period_gain * self.amplitude * self.pressure_gain
+    }
+
+    // Vocal effort, in the range 0-1. This is a shorthand for
+    // set_subglottal_pressure, with pressures spaced exponentially
+    // from the phonation threshold (0) up to a loud voice (1).
+    pub fn set_effort(&mut self, effort: f32) {
+        let effort = effort.clamp(0.0, 1.0);
+        let ratio = PRESSURE_MAX / PRESSURE_MIN;
+        self.set_subglottal_pressure(PRESSURE_MIN * ratio.powf(effort));
+    }
+
+    // Maps subglottal pressure (in Pascals) onto Rd, amplitude,
+    // aspiration and noise floor, which co-vary in real voices.
+    // These scale the values set with set_rd (or set_shape),
+    // set_amplitude, set_aspiration and set_noise_floor, which
+    // are left as they are at the nominal pressure (800Pa).
+    //
+    // Rd follows Fant's observation that the glottal pulse gets
+    // sharper with effort (about 2.7 when soft down to 0.6 when
+    // loud). Amplitude grows linearly with pressure, which is
+    // about 6dB per doubling; together with the sharper closure
+    // this lands close to the 8-9dB per doubling reported in
+    // the literature. Breathiness (aspiration and the unpulsed
+    // noise floor) falls off as the folds close more firmly.
+    //
+    // Pressure also gates voicing, with some hysteresis (see
+    // PRESSURE_OFFSET), and the breath noise level follows the
+    // airflow, which goes with the square root of the pressure.
+    pub fn set_subglottal_pressure(&mut self, pressure: f32) {
+        let pressure = pressure.max(0.0);
+        let (r_d, aspiration, noise_floor) = effort_quality(pressure);
+        let (nom_r_d, nom_aspiration, nom_noise_floor) =
+            effort_quality(PRESSURE_NOMINAL);
+
+        self.pressure_gain = pressure / PRESSURE_NOMINAL;
+        self.rd_scale = r_d / nom_r_d;
+        self.aspiration_scale = aspiration / nom_aspiration;
+        self.noise_scale = noise_floor / nom_noise_floor;
+
+        if self.voiced && pressure < PRESSURE_OFFSET {
+            self.voiced = false;
+        } else if !self.voiced && pressure >= PRESSURE_MIN {
+            self.voiced = true;
+        }
+
+        self.breath = (pressure / PRESSURE_NOMINAL).sqrt();
+    }
+
+    // true if the folds are vibrating (or starting to)
+    pub fn is_voiced(&self) -> bool {
+        self.voiced
+    }
+
+    // Amount of source-tract interaction, 0-1. When non-zero,
+    // the flow computed by the LF model is scaled by the
+    // transglottal pressure drop, sqrt(1 - coupling * p), where p
+    // is the pressure at the glottal end of the tract (see
+    // tick_with_load). 0 is a pure source-filter model.
+    pub fn set_coupling(&mut self, coupling: f32) {
+        self.coupling = coupling.clamp(0.0, 1.0);
+    }
+
+    // Blend amount (0-1) of creaky voice: irregular,
+    // low-frequency pulses.
+    pub fn set_creak(&mut self, creak: f32) {
+        self.creak = creak.clamp(0.0, 1.0);
+    }
+
+    // Blend amount (0-1) of vocal fry: short, regular pulses
+    // at a very low rate, separated by long closed phases.
+    pub fn set_fry(&mut self, fry: f32) {
+        self.fry = fry.clamp(0.0, 1.0);
+    }
+
+    // Blend amount (0-1) of diplophonia: alternating pulse
+    // amplitudes and durations (period doubling).
+    pub fn set_diplophonia(&mut self, diplophonia: f32) {
+        self.diplophonia = diplophonia.clamp(0.0, 1.0);
+    }
+
     pub fn set_aspiration(&mut self, aspiration: f32) {
         self.aspiration = aspiration;
     }
 
+    pub fn aspiration(&self) -> f32 {
+        self.aspiration
+    }
+
+    // Crossfades from voiced (0) to fully whispered (1)
+    // excitation. Whispering fades out the glottal pulse and
+    // the pitch-synchronous modulation of the noise, and moves
+    // the aspiration filters over to the whisper spectrum.
+    pub fn set_whisper(&mut self, whisper: f32) {
+        self.whisper = whisper.clamp(0.0, 1.0);
+    }
+
+    // level of the noise when fully whispering
+    pub fn set_whisper_level(&mut self, level: f32) {
+        self.whisper_level = level;
+    }
+
+    // highpass and lowpass cutoffs (Hz) of the noise when
+    // fully whispering. Whisper noise is much broader
+    // than aspiration noise.
+    pub fn set_whisper_filter(&mut self, hp_freq: f32, lp_freq: f32) {
+        self.whisper_hp_freq = hp_freq;
+        self.whisper_lp_freq = lp_freq;
+        self.update_noise_filters();
+    }
+
+    // highpass and lowpass cutoffs (Hz) of the aspiration noise
+    pub fn set_aspiration_filter(&mut self, hp_freq: f32, lp_freq: f32) {
+        self.asp_hp_freq = hp_freq;
+        self.asp_lp_freq = lp_freq;
+        self.update_noise_filters();
+    }
+
+    // Sets the aspiration filter cutoffs somewhere between the
+    // aspiration and whisper settings, depending on the whisper
+    // amount. Cutoffs are interpolated exponentially.
+    fn update_noise_filters(&mut self) {
+        let w = self.whisper;
+        let hp = self.asp_hp_freq * (self.whisper_hp_freq / self.asp_hp_freq).powf(w);
+        let lp = self.asp_lp_freq * (self.whisper_lp_freq / self.asp_lp_freq).powf(w);
+        self.asp_hpfilt.set_freq(hp);
+        self.asp_lpfilt.set_freq(lp);
+        self.pwhisper = w;
+    }
+
     pub fn set_noise_floor(&mut self, noise_floor: f32) {
         self.noise_floor = noise_floor;
     }
@@ -213,30 +606,267 @@ impl Glot {
         self.rng = seed;
     }
 
+    // period jitter, as a percentage of the period length.
+    // Capped at 50% to keep periods from collapsing.
+    pub fn set_jitter(&mut self, percent: f32) {
+        self.jitter = percent.clamp(0.0, 50.0);
+    }
+
+    // amplitude shimmer, as a percentage of the amplitude
+    pub fn set_shimmer(&mut self, percent: f32) {
+        self.shimmer = percent.clamp(0.0, 100.0);
+    }
+
+    // seeds the generator used for jitter and shimmer,
+    // independent of the aspiration noise (see srand)
+    pub fn srand_perturbation(&mut self, seed: u32) {
+        self.perturb_rng.seed(seed);
+    }
+
+    // random value in the range -1 to 1, with a triangular
+    // distribution (sum of two uniform values)
+    fn perturbation(&mut self) -> f32 {
+        self.perturb_rng.randf() + self.perturb_rng.randf() - 1.0
+    }
+
     fn rand(&mut self) -> u32 {
         self.rng = self.rng.wrapping_mul(1103515245);
         self.rng = self.rng.wrapping_add(12345) % LCG_MAX;
         return self.rng;
     }
 
+    // Band-limits the slope discontinuities of the LF waveform
+    // (the sharp turn at the glottal closure instant Te, the end
+    // of the pulse, and the period boundary) using 2-point
+    // polyBLAMP residuals. The correction spans the samples on
+    // either side of the corner, so the output is delayed by
+    // one sample.
+    fn apply_blamp(
+        &mut self,
+        cur: f32,
+        prev_t: f32,
+        t: f32,
+        boundary: Option<(f32, f32)>
+    ) -> f32 {
+        let mut cur = cur;
+        let mut prev = self.blamp_prev;
+
+        let corner = if boundary.is_some() {
+            boundary
+        } else if prev_t <= self.t_e && t > self.t_e {
+            let dt = self.onedsr / self.pulse_length;
+            let gain = self.gain();
+            let slope_change =
+                (self.return_slope(self.t_e) - self.open_slope(self.t_e)) *
+                gain * dt;
+            Some(((t - self.t_e) / dt, slope_change))
+        } else if prev_t <= 1.0 && t > 1.0 {
+            // pulse ends before the period does (closed phase)
+            let dt = self.onedsr / self.pulse_length;
+            let gain = self.gain();
+            let slope_change = -self.return_slope(1.0) * gain * dt;
+            Some(((t - 1.0) / dt, slope_change))
+        } else {
+            None
+        };
+
+        if let Some((d, slope_change)) = corner {
+            let d = d.clamp(0.0, 1.0);
+            let e = 1.0 - d;
+            prev += slope_change * d * d * d / 6.0;
+            cur += slope_change * e * e * e / 6.0;
+        }
+
+        self.blamp_prev = cur;
+        prev
+    }
+
+    pub fn set_quality(&mut self, quality: GlotQuality) {
+        self.quality = quality;
+        self.blamp_prev = 0.0;
+        self.blamp_delayed = GlotOutput::default();
+    }
+
+    // LF flow derivative at normalized time t, without gain.
+    // Past the end of the pulse (t > 1) the glottis is closed.
+    fn waveform(&self, t: f32) -> f32 {
+        if t > 1.0 {
+            0.0
+        } else if t > self.t_e {
+            (-(-self.epsilon * (t - self.t_e)).exp() + self.shift) /
+                self.delta
+        } else {
+            self.e_0 * (self.alpha * t).exp() * (self.omega * t).sin()
+        }
+    }
+
+    // Glottal flow at normalized time t: the integral of the
+    // waveform, worked out analytically for both phases.
+    fn flow_at(&self, t: f32) -> f32 {
+        if t > 1.0 {
+            0.0
+        } else if t > self.t_e {
+            let dt = t - self.t_e;
+            let ret = ((-self.epsilon * dt).exp() - 1.0) / self.epsilon;
+            self.flow_te + (ret + self.shift * dt) / self.delta
+        } else {
+            let a = self.alpha;
+            let w = self.omega;
+            let at = (a * t).exp();
+            let wt = w * t;
+            self.e_0 * (at * (a * wt.sin() - w * wt.cos()) + w) / (a * a + w * w)
+        }
+    }
+
+    // slope of the open phase, per unit of normalized time
+    fn open_slope(&self, t: f32) -> f32 {
+        let at = self.alpha * t;
+        let wt = self.omega * t;
+        self.e_0 * at.exp() * (self.alpha * wt.sin() + self.omega * wt.cos())
+    }
+
+    // slope of the return phase, per unit of normalized time
+    fn return_slope(&self, t: f32) -> f32 {
+        self.epsilon * (-self.epsilon * (t - self.t_e)).exp() / self.delta
+    }
+
+    // 1.0 if the last tick started a new period, 0.0 otherwise
+    pub fn period_trigger(&self) -> f32 {
+        if self.last_output.period_start { 1.0 } else { 0.0 }
+    }
+
+    // 1.0 if the last tick crossed the glottal closure
+    // instant, 0.0 otherwise
+    pub fn closure_trigger(&self) -> f32 {
+        if self.last_output.gci { 1.0 } else { 0.0 }
+    }
+
+    // position in the current period (0-1), from the last tick.
+    // Wraps once per period, so it can drive things like
+    // RandomLine pitch-synchronously.
+    pub fn phase(&self) -> f32 {
+        self.last_output.phase
+    }
+
+    // how open the glottis is (0-1) at the last tick, taken
+    // from the flow relative to its peak
+    pub fn opening(&self) -> f32 {
+        self.opening
+    }
+
     pub fn tick(&mut self) -> f32 {
+        let out = self.tick_multi();
+        out.flow_derivative + out.noise
+    }
+
+    // Like tick, but with the pressure at the glottal end
+    // of the tract from the previous sample (see
+    // Tract::glottal_pressure), used when coupling is enabled.
+    pub fn tick_with_load(&mut self, pressure: f32) -> f32 {
+        let out = self.tick_multi_with_load(pressure);
+        out.flow_derivative + out.noise
+    }
+
+    // Computes the next sample, returning the flow, flow
+    // derivative and noise as separate signals, along with
+    // the phase and a glottal closure instant flag.
+    pub fn tick_multi(&mut self) -> GlotOutput {
+        self.tick_multi_with_load(0.0)
+    }
+
+    pub fn tick_multi_with_load(&mut self, pressure: f32) -> GlotOutput {
         let mut out;
 
+        let prev_t = self.time_in_waveform / self.pulse_length;
+        let gain = self.gain();
+
+        // slope discontinuity at the period boundary, if
+        // crossed: (distance past it in samples, slope change)
+        let mut boundary = None;
+
         self.time_in_waveform += self.onedsr;
 
         if self.time_in_waveform > self.waveform_length {
+            // if the pulse already ended there is no slope left
+            let end_slope = if prev_t < 1.0 {
+                self.return_slope(1.0) * gain *
+                    self.onedsr / self.pulse_length
+            } else {
+                0.0
+            };
             self.time_in_waveform -= self.waveform_length;
             self.setup_waveform();
+            let start_slope =
+                self.open_slope(0.0) * self.gain() *
+                self.onedsr / self.pulse_length;
+            boundary = Some((
+                self.time_in_waveform / self.onedsr,
+                start_slope - end_slope
+            ));
         }
 
-        let t = self.time_in_waveform / self.waveform_length;
+        let t = self.time_in_waveform / self.pulse_length;
+        let gain = self.gain();
 
-        if t > self.t_e {
-            out =
-                (-(-self.epsilon * (t - self.t_e)).exp() + self.shift) /
-                self.delta;
-        } else {
-            out = self.e_0 * (self.alpha * t).exp() * (self.omega * t).sin();
+        out = self.waveform(t);
+        out *= gain;
+
+        let mut flow = self.flow_at(t) * gain;
+        let mut phase = self.time_in_waveform / self.waveform_length;
+        let mut gci = boundary.is_none() && prev_t <= self.t_e && t > self.t_e;
+        let mut period_start = boundary.is_some();
+
+        self.opening = (self.flow_at(t) / self.flow_peak).clamp(0.0, 1.0);
+
+        if let GlotQuality::PolyBlamp = self.quality {
+            out = self.apply_blamp(out, prev_t, t, boundary);
+
+            let delayed = self.blamp_delayed;
+            self.blamp_delayed.flow = flow;
+            self.blamp_delayed.phase = phase;
+            self.blamp_delayed.gci = gci;
+            self.blamp_delayed.period_start = period_start;
+            flow = delayed.flow;
+            phase = delayed.phase;
+            gci = delayed.gci;
+            period_start = delayed.period_start;
+        }
+
+        let w = self.whisper;
+
+        if w != self.pwhisper {
+            self.update_noise_filters();
+        }
+
+        let target = if self.voiced { 1.0 } else { 0.0 };
+        self.voicing += (target - self.voicing) * self.voicing_coef;
+        let v = self.voicing;
+
+        out *= (1.0 - w) * v;
+        flow *= (1.0 - w) * v;
+
+        // whispering and breathing (no voicing) hold the
+        // glottis partly open
+        self.opening = (1.0 - w) * self.opening + w * 0.5;
+        self.opening = v * self.opening + (1.0 - v) * 0.5;
+
+        if self.coupling > 0.0 {
+            // The tract is driven by the flow derivative, so the
+            // pressure it reports is a derivative too. Integrate
+            // it (with a little leak to stay DC free) to get the
+            // pressure seen by the flow.
+            let dt = self.onedsr / self.pulse_length;
+            self.load_pressure =
+                self.load_pressure * LOAD_LEAK + pressure * dt;
+
+            // Scale the flow by the pressure drop across the
+            // glottis. The derivative follows from the product
+            // rule, so the band-limited slope is kept intact.
+            let p = self.coupling * LOAD_SCALE * self.load_pressure;
+            let g = (1.0 - p).max(0.0).sqrt();
+            out = out * g + flow * (g - self.load_gain) / dt;
+            flow *= g;
+            self.load_gain = g;
         }
 
         // gaussian noise (more or less)
@@ -282,15 +912,423 @@ impl Glot {
 
         // noise floor / pulsed noise, this is just crossfading
 
-        let nf = self.noise_floor;
+        let nf = (self.noise_floor * self.noise_scale).min(1.0);
 
         env = (nf + (1.0 - nf)*env) * noise;
 
         // attenutate by aspiration level
 
-        env *= self.aspiration;
-        out += env;
-        out
+        let aspiration = self.aspiration * self.aspiration_scale;
+        env *= aspiration;
+
+        // without voicing the noise isn't pulsed anymore
+        env = v*env + (1.0 - v)*aspiration*noise;
+        env *= self.breath;
+
+        // whispering: steady (unpulsed) noise
+        env = (1.0 - w)*env + w*self.whisper_level*noise;
+
+        self.last_output = GlotOutput {
+            flow,
+            flow_derivative: out,
+            noise: env,
+            phase,
+            gci,
+            period_start,
+        };
+
+        self.last_output
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tract;
+
+    #[test]
+    fn test_triggers() {
+        let sr = 44100;
+
+        for quality in [GlotQuality::Naive, GlotQuality::PolyBlamp] {
+            let mut glot = Glot::new(sr);
+            glot.set_quality(quality);
+            glot.set_freq(100.0);
+
+            let mut periods = 0;
+            let mut closures = 0;
+            let mut last_start = -1.0;
+
+            for _ in 0..sr {
+                let out = glot.tick_multi();
+                periods += glot.period_trigger() as usize;
+                closures += glot.closure_trigger() as usize;
+
+                if out.period_start {
+                    last_start = out.phase;
+                }
+
+                // closure happens partway through the period
+                if out.gci {
+                    assert!(out.phase > 0.3 && out.phase < 1.0);
+                }
+            }
+
+            assert!((99..=101).contains(&periods), "got {} periods", periods);
+            assert!((99..=101).contains(&closures), "got {} closures", closures);
+            assert!(last_start < 0.01, "period start at phase {}", last_start);
+        }
+    }
+
+    #[test]
+    fn test_voicing_hysteresis() {
+        let mut glot = Glot::new(44100);
+        assert!(glot.is_voiced());
+
+        glot.set_subglottal_pressure(250.0);
+        assert!(glot.is_voiced());
+        glot.set_subglottal_pressure(150.0);
+        assert!(!glot.is_voiced());
+        glot.set_subglottal_pressure(250.0);
+        assert!(!glot.is_voiced());
+        glot.set_subglottal_pressure(300.0);
+        assert!(glot.is_voiced());
+
+        // fades out after voicing stops
+        glot.set_subglottal_pressure(0.0);
+        let peak = (0..44100)
+            .map(|_| glot.tick_multi().flow_derivative.abs())
+            .skip(22050)
+            .fold(0.0, f32::max);
+        assert!(peak < 1e-6);
+    }
+
+    #[test]
+    fn test_pressure_keeps_settings() {
+        let mut glot = Glot::new(44100);
+        glot.set_rd(1.2);
+        glot.set_aspiration(0.2);
+        glot.set_noise_floor(0.05);
+        glot.set_amplitude(0.8);
+
+        // loud and soft pull the voice quality both ways...
+        glot.set_effort(1.0);
+        assert!(glot.r_d * glot.rd_scale < 1.2);
+        assert!(glot.aspiration * glot.aspiration_scale < 0.2);
+        glot.set_effort(0.0);
+        assert!(glot.r_d * glot.rd_scale > 1.2);
+        assert!(glot.noise_floor * glot.noise_scale > 0.05);
+
+        // ...without touching the settings themselves, which
+        // come back at the nominal pressure
+        glot.set_subglottal_pressure(PRESSURE_NOMINAL);
+        assert_eq!((glot.r_d, glot.aspiration), (1.2, 0.2));
+        assert_eq!((glot.noise_floor, glot.amplitude), (0.05, 0.8));
+        assert!((glot.gain() / glot.period_gain - 0.8).abs() < 1e-6);
+        assert!((glot.rd_scale - 1.0).abs() < 1e-6);
+        assert!((glot.aspiration_scale - 1.0).abs() < 1e-6);
+        assert!((glot.noise_scale - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_coupling() {
+        let sr = 44100;
+        let mut plain = Glot::new(sr);
+        let mut loaded = Glot::new(sr);
+        let mut coupled = Glot::new(sr);
+        let mut tract = Tract::new(sr, 17.0, 1);
+        coupled.set_coupling(1.0);
+
+        let mut diff = 0.0;
+
+        for _ in 0..sr {
+            let a = plain.tick();
+            let b = loaded.tick_with_load(tract.glottal_pressure());
+
+            // no coupling: the load is ignored
+            assert_eq!(a, b);
+
+            let c = coupled.tick_with_load(tract.glottal_pressure());
+            let out = tract.tick(c);
+            assert!(out.is_finite() && out.abs() < 10.0);
+            diff += (c - a).abs();
+        }
+
+        assert!(diff > 1.0);
+    }
+
+    // lengths (in samples) of the periods started over n ticks
+    fn period_lengths(glot: &mut Glot, n: usize) -> Vec<usize> {
+        let mut lengths = vec![];
+        let mut count = 0;
+
+        for _ in 0..n {
+            glot.tick();
+            count += 1;
+
+            if glot.period_trigger() > 0.0 {
+                lengths.push(count);
+                count = 0;
+            }
+        }
+
+        // the first one is cut short
+        lengths.remove(0);
+        lengths
+    }
+
+    #[test]
+    fn test_no_perturbation() {
+        let sr = 44100;
+        let mut plain = Glot::new(sr);
+        let mut perturbed = Glot::new(sr);
+
+        // zeroed again, with a different seed: no trace left
+        perturbed.set_jitter(5.0);
+        perturbed.set_shimmer(10.0);
+        perturbed.srand_perturbation(1234);
+        perturbed.set_jitter(0.0);
+        perturbed.set_shimmer(0.0);
+
+        for _ in 0..sr {
+            assert_eq!(plain.tick(), perturbed.tick());
+        }
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let sr = 44100;
+        let mut glot = Glot::new(sr);
+        glot.set_freq(100.0);
+        glot.set_jitter(5.0);
+
+        let lengths = period_lengths(&mut glot, sr);
+        let shortest = *lengths.iter().min().unwrap();
+        let longest = *lengths.iter().max().unwrap();
+
+        // 441 samples, give or take 5% (and a sample of rounding)
+        assert!(longest - shortest > 4);
+        assert!(shortest >= 418 && longest <= 464);
+    }
+
+    #[test]
+    fn test_perturbation_seed() {
+        let sr = 44100;
+        let glots = [1, 1, 2].map(|seed| {
+            let mut glot = Glot::new(sr);
+            glot.set_jitter(3.0);
+            glot.set_shimmer(5.0);
+            glot.srand_perturbation(seed);
+            glot
+        });
+        let [mut a, mut b, mut c] = glots;
+
+        let mut diff = 0.0;
+
+        for _ in 0..sr {
+            let out = a.tick();
+            assert_eq!(out, b.tick());
+            diff += (out - c.tick()).abs();
+        }
+
+        assert!(diff > 1.0);
+    }
+
+    // energy above half the Nyquist frequency, relative to
+    // the total, over a second of the flow derivative
+    fn high_band_energy(glot: &mut Glot, sr: usize) -> f32 {
+        let mut hp1 = ButterworthHighPass::new(sr);
+        let mut hp2 = ButterworthHighPass::new(sr);
+        hp1.set_freq(sr as f32 / 4.0);
+        hp2.set_freq(sr as f32 / 4.0);
+
+        let mut high = 0.0;
+        let mut total = 0.0;
+
+        for i in 0..sr + sr / 10 {
+            let x = glot.tick_multi().flow_derivative;
+            let h = hp2.tick(hp1.tick(x));
+
+            // skip the filter settling
+            if i >= sr / 10 {
+                high += h * h;
+                total += x * x;
+            }
+        }
+
+        high / total
+    }
+
+    #[test]
+    fn test_polyblamp_aliasing() {
+        let sr = 44100;
+        let mut naive = Glot::new(sr);
+        let mut blamp = Glot::new(sr);
+        naive.set_freq(1500.0);
+        blamp.set_freq(1500.0);
+        blamp.set_quality(GlotQuality::PolyBlamp);
+
+        // the naive waveform folds its upper harmonics back
+        // down, a lot of them into the top of the spectrum
+        let naive = high_band_energy(&mut naive, sr);
+        let blamp = high_band_energy(&mut blamp, sr);
+        assert!(blamp < 0.7 * naive, "{} vs {}", blamp, naive);
+    }
+
+    // Over a second at 120Hz, after a second to settle: the
+    // closed quotient (no flow), energy above 4kHz relative to
+    // the total, and the output peak while the glottis is closed
+    fn phonation(glot: &mut Glot, sr: usize) -> (f32, f32, f32) {
+        let mut hp1 = ButterworthHighPass::new(sr);
+        let mut hp2 = ButterworthHighPass::new(sr);
+        hp1.set_freq(4000.0);
+        hp2.set_freq(4000.0);
+        glot.set_freq(120.0);
+
+        let mut closed = 0;
+        let mut closed_peak = 0.0f32;
+        let mut high = 0.0;
+        let mut total = 0.0;
+
+        for i in 0..2 * sr {
+            let out = glot.tick_multi();
+            let x = out.flow_derivative;
+            let h = hp2.tick(hp1.tick(x));
+
+            if i < sr {
+                continue;
+            }
+
+            if out.flow == 0.0 {
+                closed += 1;
+                closed_peak = closed_peak.max((x + out.noise).abs());
+            }
+
+            high += h * h;
+            total += x * x;
+        }
+
+        (closed as f32 / sr as f32, high / total, closed_peak)
+    }
+
+    #[test]
+    fn test_phonation_modes() {
+        let sr = 44100;
+        let glot = || {
+            let mut glot = Glot::new(sr);
+            glot.set_aspiration(0.0);
+            glot
+        };
+
+        let (modal_cq, modal_hf, _) = phonation(&mut glot(), sr);
+
+        // fry is pressed: short pulses with a long closed phase,
+        // and sharper closures
+        let mut fry = glot();
+        fry.set_fry(1.0);
+        let (fry_cq, fry_hf, closed_peak) = phonation(&mut fry, sr);
+        assert!(modal_cq < 0.05 && fry_cq > 0.5);
+        assert!(fry_hf > 2.0 * modal_hf);
+        assert_eq!(closed_peak, 0.0);
+
+        // made breathy, noise gets through while it's closed
+        fry.set_aspiration(0.5);
+        fry.set_noise_floor(0.3);
+        let (_, _, closed_peak) = phonation(&mut fry, sr);
+        assert!(closed_peak > 0.01);
+
+        // creak: slower than modal, and irregular
+        let mut creak = glot();
+        creak.set_freq(120.0);
+        creak.set_creak(1.0);
+        let lengths = period_lengths(&mut creak, sr);
+        let shortest = *lengths.iter().min().unwrap();
+        let longest = *lengths.iter().max().unwrap();
+        assert!(shortest > 400 && longest - shortest > 50);
+
+        // diplophonia: alternating long and short periods
+        let mut diplo = glot();
+        diplo.set_freq(120.0);
+        diplo.set_diplophonia(1.0);
+        let lengths = period_lengths(&mut diplo, sr);
+        for pair in lengths.windows(2) {
+            assert!(pair[0].abs_diff(pair[1]) > 50);
+        }
+    }
+
+    // normalized autocorrelation at the given lag
+    fn autocorrelation(x: &[f32], lag: usize) -> f32 {
+        let r: f32 = x.iter().zip(&x[lag..]).map(|(a, b)| a * b).sum();
+        let e: f32 = x.iter().map(|a| a * a).sum();
+        r / e
+    }
+
+    #[test]
+    fn test_whisper() {
+        let sr = 44100;
+        let mut glot = Glot::new(sr);
+        glot.set_freq(100.0);
+
+        let voiced: Vec<f32> = (0..sr / 2).map(|_| glot.tick()).collect();
+
+        glot.set_whisper(1.0);
+        let mut pulse = 0.0f32;
+        let whispered: Vec<f32> = (0..sr / 2)
+            .map(|_| {
+                let out = glot.tick_multi();
+                pulse = pulse.max(out.flow_derivative.abs());
+                out.flow_derivative + out.noise
+            })
+            .collect();
+
+        // no pulse left, just noise, which doesn't repeat
+        // every period like the voiced output does
+        let rms = (whispered.iter().map(|x| x * x).sum::<f32>() / whispered.len() as f32).sqrt();
+        assert_eq!(pulse, 0.0);
+        assert!(rms > 0.01);
+        assert!(autocorrelation(&voiced, 441) > 0.5);
+        assert!(autocorrelation(&whispered, 441).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_flow_integrates_derivative() {
+        let sr = 44100;
+        let freq = 100.0;
+        let mut glot = Glot::new(sr);
+        glot.set_freq(freq);
+
+        // run up to the start of a period
+        while !glot.tick_multi().period_start {}
+
+        // flow is over normalized time, so the running sum is
+        // scaled by the period (cumsum / sr, times f0)
+        let mut flow = glot.last_output.flow;
+        let mut peak = 0.0f32;
+        let mut worst = 0.0f32;
+        let mut prev = glot.last_output.flow_derivative;
+        let mut end = flow;
+
+        loop {
+            let out = glot.tick_multi();
+
+            if out.period_start {
+                break;
+            }
+
+            // trapezoidal
+            flow += 0.5 * (prev + out.flow_derivative) * freq / sr as f32;
+            prev = out.flow_derivative;
+            peak = peak.max(out.flow);
+            end = out.flow;
+            worst = worst.max((flow - out.flow).abs());
+        }
+
+        assert!(peak > 0.0);
+        assert!(worst < 0.01 * peak, "off by {} (peak {})", worst, peak);
+
+        // and back to (about) nothing by the end of the period
+        assert!(flow.abs() < 0.01 * peak);
+        assert!(end.abs() < 0.01 * peak);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::tuning::mtof;
+use crate::Glot;
+
+/// Common interface for glottal excitation models. Anything
+/// implementing this can be used as the source of a Voice.
+pub trait GlottalSource {
+    fn set_freq(&mut self, freq: f32);
+
+    // pitch is in MIDI note numbers (12-TET). Voices resolve
+    // pitch through their own Tuning and call set_freq instead.
+    fn set_pitch(&mut self, pitch: f32) {
+        self.set_freq(mtof(pitch));
+    }
+
+    // returns the next sample of the glottal flow derivative,
+    // roughly normalized to have a negative peak of -1
+    fn tick(&mut self) -> f32;
+
+    // Trigger signals (1.0 or 0.0) from the last tick, for
+    // syncing other things to the glottal cycle: one at the
+    // start of each period, one at the glottal closure instant.
+    fn period_trigger(&self) -> f32;
+    fn closure_trigger(&self) -> f32;
+
+    // position in the current glottal period, 0-1
+    fn phase(&self) -> f32;
+
+    // how open the glottis is at the last tick, from 0
+    // (closed) to 1 (fully open). Used to couple the tract
+    // to the trachea, see Tract::set_glottal_opening.
+    fn opening(&self) -> f32;
+
+    // Subglottal (lung) pressure, in Pascals. Sources without
+    // a notion of pressure ignore this.
+    fn set_subglottal_pressure(&mut self, _pressure: f32) {}
+
+    // Level of the aspiration (breath) noise. Sources without
+    // a noise component ignore this.
+    fn set_aspiration(&mut self, _aspiration: f32) {}
+
+    // Amount of source-tract interaction, 0-1. Sources that
+    // don't model it ignore this.
+    fn set_coupling(&mut self, _coupling: f32) {}
+
+    // Like tick, but given the pressure at the glottal end of
+    // the tract from the previous sample, so the flow can react
+    // to the acoustic load. Defaults to a plain tick.
+    fn tick_with_load(&mut self, _pressure: f32) -> f32 {
+        self.tick()
+    }
+}
+
+impl GlottalSource for Glot {
+    fn set_freq(&mut self, freq: f32) {
+        Glot::set_freq(self, freq);
+    }
+
+    fn set_pitch(&mut self, pitch: f32) {
+        Glot::set_pitch(self, pitch);
+    }
+
+    fn tick(&mut self) -> f32 {
+        Glot::tick(self)
+    }
+
+    fn period_trigger(&self) -> f32 {
+        Glot::period_trigger(self)
+    }
+
+    fn closure_trigger(&self) -> f32 {
+        Glot::closure_trigger(self)
+    }
+
+    fn phase(&self) -> f32 {
+        Glot::phase(self)
+    }
+
+    fn opening(&self) -> f32 {
+        Glot::opening(self)
+    }
+
+    fn set_subglottal_pressure(&mut self, pressure: f32) {
+        Glot::set_subglottal_pressure(self, pressure)
+    }
+
+    fn set_aspiration(&mut self, aspiration: f32) {
+        Glot::set_aspiration(self, aspiration)
+    }
+
+    fn set_coupling(&mut self, coupling: f32) {
+        Glot::set_coupling(self, coupling)
+    }
+
+    fn tick_with_load(&mut self, pressure: f32) -> f32 {
+        Glot::tick_with_load(self, pressure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KLGlott88;
+    use crate::Rosenberg;
+    use crate::TwoMass;
+
+    // (negative peak, pulses) over a second at 200Hz, after
+    // letting the source settle. A pulse is a dip below -0.5.
+    fn pulses(src: &mut impl GlottalSource, sr: usize) -> (f32, usize) {
+        src.set_freq(200.0);
+
+        for _ in 0..sr / 2 {
+            src.tick();
+        }
+
+        let mut prev = 0.0;
+        let mut lo = 0.0f32;
+        let mut count = 0;
+
+        for _ in 0..sr {
+            let x = src.tick();
+            if prev > -0.5 && x <= -0.5 {
+                count += 1;
+            }
+            lo = lo.min(x);
+            prev = x;
+        }
+
+        (lo, count)
+    }
+
+    #[test]
+    fn test_sources() {
+        let sr = 44100;
+
+        let results = [
+            pulses(&mut Rosenberg::new(sr), sr),
+            pulses(&mut KLGlott88::new(sr), sr),
+            pulses(&mut TwoMass::new(sr), sr),
+        ];
+
+        // normalized to a negative peak of about -1, with one
+        // pulse per period (the two-mass model only roughly
+        // follows the requested frequency)
+        for (lo, count) in results {
+            assert!(lo > -1.2 && lo < -0.5);
+            assert!((180..=220).contains(&count));
+        }
+    }
+
+    // (period, closure) trigger counts over n ticks
+    fn count_triggers(src: &mut impl GlottalSource, n: usize) -> (usize, usize) {
+        (0..n).fold((0, 0), |(p, c), _| {
+            src.tick();
+            (
+                p + src.period_trigger() as usize,
+                c + src.closure_trigger() as usize,
+            )
+        })
+    }
+
+    #[test]
+    fn test_triggers_at_rest() {
+        let sr = 44100;
+
+        // a stopped phasor is not a new period on every tick
+        let mut rb = Rosenberg::new(sr);
+        rb.set_freq(0.0);
+        assert_eq!(count_triggers(&mut rb, 1000), (0, 0));
+
+        let mut kl = KLGlott88::new(sr);
+        kl.set_freq(0.0);
+        assert_eq!(count_triggers(&mut kl, 1000), (0, 0));
+    }
+
+    #[test]
+    fn test_leaky_closure() {
+        let sr = 44100;
+        let mut tm = TwoMass::new(sr);
+
+        // folds that never fully close still mark a closure
+        // once per period
+        tm.set_rest_area(0.15);
+        count_triggers(&mut tm, sr / 2);
+        let (periods, closures) = count_triggers(&mut tm, sr / 2);
+        let min_flow = (0..sr / 10).map(|_| {
+            tm.tick();
+            tm.flow()
+        });
+
+        assert!(min_flow.fold(f32::MAX, f32::min) > 0.0);
+        assert!(periods > 30);
+        assert!(closures.abs_diff(periods) <= 1);
+    }
+}
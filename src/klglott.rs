@@ -0,0 +1,136 @@
+use crate::GlottalSource;
+use std::f32::consts::PI;
+
+// KLGLOTT88 glottal source, as used in the Klatt synthesizer
+// (Klatt and Klatt, 1990). During the open phase the flow
+// is a polynomial (a*t^2 - b*t^3), which is closed abruptly
+// at the end of the open phase. Spectral tilt is applied
+// afterwards with a one-pole lowpass filter.
+pub struct KLGlott88 {
+    freq: f32,
+    sr: usize,
+    onedsr: f32,
+    phs: f32,
+    open_quotient: f32,
+
+    // spectral tilt, in dB of attenuation at 3kHz
+    tilt: f32,
+    ptilt: f32,
+    a1: f32,
+    y: f32,
+
+    // phase and triggers from the last tick
+    last_phs: f32,
+    period_trig: f32,
+    closure_trig: f32,
+}
+
+impl KLGlott88 {
+    pub fn new(sr: usize) -> Self {
+        KLGlott88 {
+            freq: 140.0,
+            sr,
+            onedsr: 1.0 / sr as f32,
+            phs: 0.0,
+            open_quotient: 0.6,
+            tilt: 0.0,
+            ptilt: -1.0,
+            a1: 0.0,
+            y: 0.0,
+            last_phs: 0.0,
+            period_trig: 0.0,
+            closure_trig: 0.0,
+        }
+    }
+
+    pub fn set_open_quotient(&mut self, oq: f32) {
+        self.open_quotient = oq.clamp(0.1, 1.0);
+    }
+
+    pub fn set_tilt(&mut self, tilt_db: f32) {
+        self.tilt = tilt_db.max(0.0);
+    }
+
+    fn update_tilt(&mut self) {
+        self.ptilt = self.tilt;
+
+        if self.tilt <= 0.0 {
+            self.a1 = 0.0;
+            return;
+        }
+
+        // find the one-pole coefficient that gives the requested
+        // attenuation at 3kHz, solving |H(w)| = g for a1
+        let g = (10.0_f32).powf(-self.tilt / 20.0);
+        let g2 = g * g;
+        let c = (2.0 * PI * 3000.0 / self.sr as f32).cos();
+        let b = (1.0 - g2 * c) / (1.0 - g2);
+        self.a1 = b - (b * b - 1.0).sqrt();
+    }
+}
+
+impl GlottalSource for KLGlott88 {
+    fn set_freq(&mut self, freq: f32) {
+        self.freq = freq;
+    }
+
+    fn tick(&mut self) -> f32 {
+        if self.tilt != self.ptilt {
+            self.update_tilt();
+        }
+
+        let t = self.phs;
+        let oq = self.open_quotient;
+        self.period_trig = if t < self.last_phs { 1.0 } else { 0.0 };
+
+        // closure happens abruptly at the end of the open phase
+        self.closure_trig = if self.last_phs < oq && t >= oq {
+            1.0
+        } else {
+            0.0
+        };
+        self.last_phs = t;
+
+        let tau = t / oq;
+
+        // derivative of tau^2 - tau^3, scaled so that the
+        // value at closure is -1
+        let out = if tau < 1.0 {
+            2.0 * tau - 3.0 * tau * tau
+        } else {
+            0.0
+        };
+
+        self.phs += self.freq * self.onedsr;
+
+        if self.phs >= 1.0 {
+            self.phs -= 1.0;
+        }
+
+        self.y = (1.0 - self.a1) * out + self.a1 * self.y;
+        self.y
+    }
+
+    fn period_trigger(&self) -> f32 {
+        self.period_trig
+    }
+
+    fn closure_trigger(&self) -> f32 {
+        self.closure_trig
+    }
+
+    fn phase(&self) -> f32 {
+        self.last_phs
+    }
+
+    fn opening(&self) -> f32 {
+        let tau = self.last_phs / self.open_quotient;
+
+        // tau^2 - tau^3 peaks at 4/27 (tau = 2/3)
+        if tau < 1.0 {
+            (tau * tau - tau * tau * tau) * 27.0 / 4.0
+        } else {
+            0.0
+        }
+    }
+}
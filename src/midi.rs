@@ -0,0 +1,443 @@
+// MIDI 1.0 byte stream parsing, for live control of a Voice
+// or a VoiceBank.
+//
+// MidiParser turns bytes into MidiMessages (running status
+// included; realtime, system common and sysex messages are
+// skipped). MidiInput queues messages with a sample offset, and
+// applies them to a MidiTarget at the right sample.
+//
+// Notes go through Voice::note_on and note_off: velocity sets
+// the subglottal pressure, overlapping notes glide legato, and
+// releasing the last note closes the voice's note gate. Pitch
+// bend is +/- 2 semitones. Controllers:
+//
+// CC1 (modulation): vibrato depth, 0 to 1 semitone
+// CC16: tongue position (back to front)
+// CC17: tongue diameter (closed to open)
+// CC18: velum, closed to open
+// CC19: aspiration noise
+// CC20: tract length, 9 to 21 cm
+// CC120, CC123: all sound/notes off
+//
+// Channel pressure (aftertouch) also sets the vibrato depth.
+
+use std::collections::VecDeque;
+use crate::GlottalSource;
+use crate::Voice;
+use crate::VoiceBank;
+
+const BEND_RANGE: f32 = 2.0;
+const MAX_VIBRATO: f32 = 1.0;
+const MAX_VELUM: f32 = 0.4;
+const MAX_ASPIRATION: f32 = 0.5;
+const MIN_LENGTH: f32 = 9.0;
+const MAX_LENGTH: f32 = 21.0;
+
+pub const CC_MODULATION: u8 = 1;
+pub const CC_TONGUE_POSITION: u8 = 16;
+pub const CC_TONGUE_DIAMETER: u8 = 17;
+pub const CC_VELUM: u8 = 18;
+pub const CC_ASPIRATION: u8 = 19;
+pub const CC_TRACT_LENGTH: u8 = 20;
+pub const CC_ALL_SOUND_OFF: u8 = 120;
+pub const CC_ALL_NOTES_OFF: u8 = 123;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    // -8192 to 8191
+    PitchBend { channel: u8, value: i16 },
+}
+
+impl MidiMessage {
+    pub fn channel(&self) -> u8 {
+        match *self {
+            MidiMessage::NoteOff { channel, .. } => channel,
+            MidiMessage::NoteOn { channel, .. } => channel,
+            MidiMessage::PolyPressure { channel, .. } => channel,
+            MidiMessage::ControlChange { channel, .. } => channel,
+            MidiMessage::ProgramChange { channel, .. } => channel,
+            MidiMessage::ChannelPressure { channel, .. } => channel,
+            MidiMessage::PitchBend { channel, .. } => channel,
+        }
+    }
+}
+
+// Byte at a time MIDI parser
+#[derive(Default)]
+pub struct MidiParser {
+    // status of the message being read, kept for running status
+    status: Option<u8>,
+    data: [u8; 2],
+    count: usize,
+}
+
+impl MidiParser {
+    pub fn new() -> Self {
+        MidiParser::default()
+    }
+
+    // Reads one byte, returning a message once one is complete
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        match byte {
+            // realtime messages can show up anywhere, even in
+            // the middle of another message, and are ignored
+            0xf8..=0xff => None,
+
+            // sysex and system common messages cancel
+            // running status, their data is skipped
+            0xf0..=0xf7 => {
+                self.status = None;
+                self.count = 0;
+                None
+            }
+
+            0x80..=0xef => {
+                self.status = Some(byte);
+                self.count = 0;
+                None
+            }
+
+            _ => {
+                let status = self.status?;
+
+                self.data[self.count] = byte;
+                self.count += 1;
+
+                let needed = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+
+                if self.count < needed {
+                    return None;
+                }
+
+                self.count = 0;
+                Some(message(status, self.data))
+            }
+        }
+    }
+
+    // parses a whole buffer
+    pub fn parse(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        bytes.iter().filter_map(|&b| self.push(b)).collect()
+    }
+}
+
+fn message(status: u8, data: [u8; 2]) -> MidiMessage {
+    let channel = status & 0x0f;
+    let [a, b] = data;
+
+    match status & 0xf0 {
+        0x80 => MidiMessage::NoteOff { channel, note: a, velocity: b },
+        // note on with velocity 0 is a note off
+        0x90 if b == 0 => MidiMessage::NoteOff { channel, note: a, velocity: 0 },
+        0x90 => MidiMessage::NoteOn { channel, note: a, velocity: b },
+        0xa0 => MidiMessage::PolyPressure { channel, note: a, pressure: b },
+        0xb0 => MidiMessage::ControlChange { channel, controller: a, value: b },
+        0xc0 => MidiMessage::ProgramChange { channel, program: a },
+        0xd0 => MidiMessage::ChannelPressure { channel, pressure: a },
+        _ => MidiMessage::PitchBend {
+            channel,
+            value: ((b as i16) << 7 | a as i16) - 8192,
+        },
+    }
+}
+
+// Something MIDI messages can be applied to
+pub trait MidiTarget {
+    fn midi(&mut self, msg: &MidiMessage);
+}
+
+fn bend_semitones(value: i16) -> f32 {
+    value as f32 / 8192.0 * BEND_RANGE
+}
+
+// applies a controller to a voice, unmapped ones are ignored
+fn control_change<G: GlottalSource>(voice: &mut Voice<G>, controller: u8, value: u8) {
+    let v = value as f32 / 127.0;
+
+    match controller {
+        CC_MODULATION => voice.vibrato_depth(v * MAX_VIBRATO),
+        CC_TONGUE_POSITION => {
+            let (_, diam) = voice.tract.tongue();
+            voice.tract.tongue_shape(v, diam);
+        }
+        CC_TONGUE_DIAMETER => {
+            let (pos, _) = voice.tract.tongue();
+            voice.tract.tongue_shape(pos, v);
+        }
+        CC_VELUM => voice.nose.set_velum(v * MAX_VELUM),
+        CC_ASPIRATION => voice.glottis.set_aspiration(v * MAX_ASPIRATION),
+        CC_TRACT_LENGTH => voice.set_length(MIN_LENGTH + v * (MAX_LENGTH - MIN_LENGTH)),
+        _ => {}
+    }
+}
+
+// A single voice plays monophonically, overlapping notes
+// are legato (see Voice::note_on)
+impl<G: GlottalSource> MidiTarget for Voice<G> {
+    fn midi(&mut self, msg: &MidiMessage) {
+        match *msg {
+            MidiMessage::NoteOn { note, velocity, .. } => {
+                self.note_on(note as f32, velocity as f32 / 127.0);
+            }
+            MidiMessage::NoteOff { note, .. } => self.note_off(note as f32),
+            MidiMessage::ControlChange { controller, value, .. } => {
+                if let CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF = controller {
+                    self.all_notes_off();
+                } else {
+                    control_change(self, controller, value);
+                }
+            }
+            MidiMessage::ChannelPressure { pressure, .. } => {
+                control_change(self, CC_MODULATION, pressure);
+            }
+            MidiMessage::PitchBend { value, .. } => self.bend = bend_semitones(value),
+            _ => {}
+        }
+    }
+}
+
+// Controllers, bend and pressure apply to every voice
+impl<G: GlottalSource> MidiTarget for VoiceBank<G> {
+    fn midi(&mut self, msg: &MidiMessage) {
+        match *msg {
+            MidiMessage::NoteOn { note, velocity, .. } => {
+                self.note_on(note as f32, velocity as f32 / 127.0);
+            }
+            MidiMessage::NoteOff { note, .. } => self.note_off(note as f32),
+            MidiMessage::ControlChange { controller, value, .. } => {
+                if let CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF = controller {
+                    self.all_notes_off();
+                } else {
+                    for i in 0..self.len() {
+                        control_change(self.voice(i), controller, value);
+                    }
+                }
+            }
+            MidiMessage::ChannelPressure { pressure, .. } => {
+                for i in 0..self.len() {
+                    control_change(self.voice(i), CC_MODULATION, pressure);
+                }
+            }
+            MidiMessage::PitchBend { value, .. } => {
+                for i in 0..self.len() {
+                    self.voice(i).bend = bend_semitones(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Schedules incoming MIDI for sample accurate playback. Bytes
+// come in with an offset (in samples) from the current time,
+// typically their position in the audio block being rendered;
+// tick applies what is due before each sample.
+#[derive(Default)]
+pub struct MidiInput {
+    parser: MidiParser,
+    queue: VecDeque<(u64, MidiMessage)>,
+    time: u64,
+    channel: Option<u8>,
+}
+
+impl MidiInput {
+    pub fn new() -> Self {
+        MidiInput::default()
+    }
+
+    // only listen to one channel (0-15), or all of them (None)
+    pub fn set_channel(&mut self, channel: Option<u8>) {
+        self.channel = channel;
+    }
+
+    // Queues bytes, to be applied offset samples from now.
+    // Messages can be split across calls.
+    pub fn push(&mut self, offset: usize, bytes: &[u8]) {
+        let time = self.time + offset as u64;
+
+        for &b in bytes {
+            if let Some(msg) = self.parser.push(b) {
+                self.push_message(time, msg);
+            }
+        }
+    }
+
+    fn push_message(&mut self, time: u64, msg: MidiMessage) {
+        if let Some(ch) = self.channel {
+            if msg.channel() != ch {
+                return;
+            }
+        }
+
+        // keep the queue in time order, and messages with
+        // the same time in the order they came in
+        let pos = self.queue.partition_point(|(t, _)| *t <= time);
+        self.queue.insert(pos, (time, msg));
+    }
+
+    // number of messages waiting
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    // Applies the messages due at this sample, then moves
+    // one sample forward. Call once per sample, before
+    // ticking the target.
+    pub fn tick<T: MidiTarget>(&mut self, target: &mut T) {
+        while let Some(&(t, msg)) = self.queue.front() {
+            if t > self.time {
+                break;
+            }
+
+            target.midi(&msg);
+            self.queue.pop_front();
+        }
+
+        self.time += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VoicePreset;
+
+    #[test]
+    fn test_running_status() {
+        let mut p = MidiParser::new();
+
+        // note on, two more through running status (the second
+        // one being a note off), with a clock byte in the middle
+        let msgs = p.parse(&[0x91, 60, 100, 64, 0xf8, 90, 60, 0]);
+        assert_eq!(msgs, [
+            MidiMessage::NoteOn { channel: 1, note: 60, velocity: 100 },
+            MidiMessage::NoteOn { channel: 1, note: 64, velocity: 90 },
+            MidiMessage::NoteOff { channel: 1, note: 60, velocity: 0 },
+        ]);
+
+        // one data byte messages, and pitch bend
+        let msgs = p.parse(&[0xd0, 10, 20, 0xe2, 0x00, 0x40, 0x7f, 0x7f]);
+        assert_eq!(msgs, [
+            MidiMessage::ChannelPressure { channel: 0, pressure: 10 },
+            MidiMessage::ChannelPressure { channel: 0, pressure: 20 },
+            MidiMessage::PitchBend { channel: 2, value: 0 },
+            MidiMessage::PitchBend { channel: 2, value: 8191 },
+        ]);
+
+        // sysex is skipped, and cancels running status
+        let msgs = p.parse(&[0xf0, 0x7e, 0x01, 0xf7, 0x10, 0xb0, 1, 64]);
+        assert_eq!(msgs, [
+            MidiMessage::ControlChange { channel: 0, controller: 1, value: 64 },
+        ]);
+
+        // a message split over two buffers
+        assert!(p.parse(&[0x80, 60]).is_empty());
+        assert_eq!(p.parse(&[0]), [
+            MidiMessage::NoteOff { channel: 0, note: 60, velocity: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_sample_accurate() {
+        let mut voice = Voice::new(44100, 17.0, 1);
+        let mut input = MidiInput::new();
+
+        // out of order offsets, within one block
+        input.push(100, &[0x90, 67, 100]);
+        input.push(10, &[0x90, 60, 100]);
+        input.push(100, &[0xe0, 0x00, 0x60]);
+        input.push(50, &[0x90, 64, 100, 0xb0, CC_TRACT_LENGTH, 127]);
+
+        let mut pitches = vec![];
+        for _ in 0..200 {
+            input.tick(&mut voice);
+            pitches.push(voice.pitch + voice.bend);
+        }
+
+        assert_eq!(pitches[9], 60.0);
+        assert_eq!(pitches[10], 60.0);
+        assert_eq!(pitches[49], 60.0);
+        assert_eq!(pitches[50], 64.0);
+        assert_eq!(pitches[100], 68.0);
+        assert_eq!(input.pending(), 0);
+
+        // channel filter
+        input.set_channel(Some(3));
+        input.push(0, &[0x90, 50, 100, 0x93, 52, 100]);
+        input.tick(&mut voice);
+        assert_eq!(voice.pitch, 52.0);
+    }
+
+    #[test]
+    fn test_legato() {
+        let mut voice = Voice::new(44100, 17.0, 1);
+        voice.set_portamento(100.0);
+        let mut input = MidiInput::new();
+
+        // overlapping notes glide, separate ones don't
+        input.push(0, &[0x90, 60, 100]);
+        input.push(441, &[0x90, 67, 100, 0x80, 60, 0]);
+        input.push(13230, &[0x80, 67, 0]);
+        input.push(17640, &[0x90, 55, 100]);
+
+        let mut pitches = vec![];
+        for _ in 0..17641 {
+            input.tick(&mut voice);
+            voice.tick();
+            pitches.push(voice.pitch);
+        }
+
+        assert_eq!(pitches[440], 60.0);
+        assert!(pitches[2646] > 60.0 && pitches[2646] < 67.0);
+        assert_eq!(pitches[8820], 67.0);
+        assert_eq!(pitches[17640], 55.0);
+        assert_eq!(voice.notes_held(), 1);
+    }
+
+    #[test]
+    fn test_controllers_persist() {
+        let mut voice = Voice::new(44100, 17.0, 1);
+        let mut input = MidiInput::new();
+
+        input.push(0, &[0xb0, CC_ASPIRATION, 127]);
+        input.tick(&mut voice);
+        let aspiration = voice.glottis.aspiration();
+
+        // notes change the pressure, not the aspiration setting
+        input.push(0, &[0x90, 60, 20, 0x80, 60, 0, 0x90, 64, 127]);
+        input.tick(&mut voice);
+        assert_eq!(voice.glottis.aspiration(), aspiration);
+    }
+
+    #[test]
+    fn test_voice_bank() {
+        let mut bank = VoiceBank::new(44100, 3, &VoicePreset::default());
+        let mut input = MidiInput::new();
+
+        input.push(0, &[0x90, 60, 100, 64, 100, 67, 100]);
+        input.tick(&mut bank);
+        assert_eq!(bank.active_notes(), 3);
+
+        input.push(0, &[0x80, 64, 0, 0xe0, 0x00, 0x20]);
+        input.tick(&mut bank);
+        assert_eq!(bank.active_notes(), 2);
+        assert_eq!(bank.voice(2).bend, -1.0);
+
+        input.push(0, &[0xb0, CC_ALL_NOTES_OFF, 0]);
+        input.tick(&mut bank);
+        assert_eq!(bank.active_notes(), 0);
+    }
+}
@@ -0,0 +1,543 @@
+use std::fmt;
+
+use crate::Behavior;
+use crate::EventfulGesture;
+use crate::GestureEventError;
+use crate::GestureVertex;
+use crate::LinearGestureBuilder;
+
+// A compact text notation for gesture paths:
+//
+// 66 1/2 gm; 68 1/4 lin; eb4 3/4 gliss(0.6,2); r 1/2
+//
+// Each vertex is a value, then an optional rate and
+// behavior, in either order. Vertices end with ';' or the
+// end of a line. A rate or behavior that is left out is
+// taken from the vertex before (1/1 and gm to begin with).
+//
+// Values are numbers (usually MIDI notes), note names with
+// c4 = 60 ("c4", "f#3", "bb2"), or a rest ("r", "rest"),
+// which holds the previous value.
+//
+// Rates are num/den, or just num, in clock periods, as in
+// GestureVertex: 1/2 lasts two periods, 2 lasts half of one.
+//
+// Behaviors: step, lin, gt, gs, gm, gl, gh (gliss tiny to
+// huge), zero, one, gate, smooth, exp(curvature),
+// sig(steepness), gliss(position,exponent) and
+// bez(x1,y1,x2,y2).
+//
+// "|:" and ":|" repeat what is between them, twice, or N
+// times with ":|N". They can be nested. "loop" loops the
+// whole path when it is played by a LinearGestureBuilder.
+//
+// '#' at the start of a word comments out the rest of
+// the line.
+
+// paths longer than this (after repeats) are an error, so that
+// a large repeat count can't take all the memory
+const MAX_VERTICES: usize = 1 << 16;
+
+// scalar, rate, behavior and wait, see GesturePath::enqueue
+const EVENTS_PER_VERTEX: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotationError {
+    // 1-indexed
+    pub line: usize,
+    pub column: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.msg
+        )
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl Token<'_> {
+    // separators, and the words that stand on their own
+    fn ends_vertex(&self) -> bool {
+        matches!(self.text, ";" | "loop" | "|:") || self.text.starts_with(":|")
+    }
+
+    fn error(&self, msg: String) -> NotationError {
+        NotationError {
+            line: self.line,
+            column: self.column,
+            msg,
+        }
+    }
+}
+
+// Splits the text into words, with ';' and line ends as
+// words of their own (both as ";"). Parentheses keep
+// what's inside them in one word, spaces and all.
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, NotationError> {
+    let mut tokens = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let mut chars = line.char_indices().enumerate().peekable();
+
+        while let Some((col, (start, c))) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            let token = |end: usize| Token {
+                text: &line[start..end],
+                line: i + 1,
+                column: col + 1,
+            };
+
+            if c == ';' {
+                tokens.push(token(start + 1));
+                continue;
+            }
+
+            if c == '#' {
+                break;
+            }
+
+            let mut depth = if c == '(' { 1 } else { 0 };
+            let mut end = start + c.len_utf8();
+
+            while let Some(&(_, (pos, c))) = chars.peek() {
+                if depth == 0 && (c.is_whitespace() || c == ';') {
+                    break;
+                }
+
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+
+                end = pos + c.len_utf8();
+                chars.next();
+            }
+
+            if depth > 0 {
+                let t = token(end);
+                return Err(t.error(format!("unclosed '(' in '{}'", t.text)));
+            }
+
+            tokens.push(token(end));
+        }
+
+        tokens.push(Token {
+            text: ";",
+            line: i + 1,
+            column: line.chars().count() + 1,
+        });
+    }
+
+    Ok(tokens)
+}
+
+// note names, with c4 = 60
+fn parse_note(s: &str) -> Option<f32> {
+    let mut chars = s.chars();
+
+    let pc = match chars.next()?.to_ascii_lowercase() {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let octave_start = rest.find(|c: char| c == '-' || c.is_ascii_digit())?;
+    let (accidentals, octave) = rest.split_at(octave_start);
+
+    let mut offset = 0;
+
+    for c in accidentals.chars() {
+        match c {
+            '#' => offset += 1,
+            'b' => offset -= 1,
+            _ => return None,
+        }
+    }
+
+    let octave: i32 = octave.parse().ok()?;
+
+    Some((12 * (octave + 1) + pc + offset) as f32)
+}
+
+fn parse_rate(s: &str) -> Option<(u32, u32)> {
+    let (num, den) = match s.split_once('/') {
+        Some((num, den)) => (num.parse().ok()?, den.parse().ok()?),
+        None => (s.parse().ok()?, 1),
+    };
+
+    if num == 0 || den == 0 {
+        return None;
+    }
+
+    Some((num, den))
+}
+
+fn parse_behavior(tok: &Token) -> Result<Option<Behavior>, NotationError> {
+    let (name, args) = match tok.text.split_once('(') {
+        Some((name, args)) => {
+            let args = args
+                .strip_suffix(')')
+                .ok_or_else(|| tok.error(format!("expected ')' at the end of '{}'", tok.text)))?;
+
+            let args = args
+                .split(',')
+                .map(|a| a.trim().parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| tok.error(format!("invalid arguments in '{}'", tok.text)))?;
+
+            (name, Some(args))
+        }
+        None => (tok.text, None),
+    };
+
+    let bhvr = match (name, args.as_deref()) {
+        ("step", None) => Behavior::Step,
+        ("lin" | "linear", None) => Behavior::Linear,
+        ("gt", None) => Behavior::GlissTiny,
+        ("gs", None) => Behavior::GlissSmall,
+        ("gm", None) => Behavior::GlissMedium,
+        ("gl", None) => Behavior::GlissLarge,
+        ("gh", None) => Behavior::GlissHuge,
+        ("zero", None) => Behavior::Zero,
+        ("one", None) => Behavior::One,
+        ("gate", None) => Behavior::Gate,
+        ("smooth", None) => Behavior::Smoothstep,
+        ("exp", Some(&[c])) => Behavior::Exponential(c),
+        ("sig", Some(&[k])) => Behavior::Sigmoid(k),
+        ("gliss", Some(&[pos, exponent])) => Behavior::Gliss(pos, exponent),
+        ("bez", Some(&[x1, y1, x2, y2])) => Behavior::Bezier(x1, y1, x2, y2),
+        ("exp" | "sig" | "gliss" | "bez", _) => {
+            return Err(tok.error(format!("wrong number of arguments in '{}'", tok.text)));
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(bhvr))
+}
+
+#[derive(Clone)]
+pub struct GesturePath {
+    pub vertices: Vec<GestureVertex<f32>>,
+    pub looped: bool,
+}
+
+impl GesturePath {
+    pub fn parse(text: &str) -> Result<Self, NotationError> {
+        let tokens = tokenize(text)?;
+
+        let mut vertices: Vec<GestureVertex<f32>> = vec![];
+        let mut looped = false;
+
+        // where each open repeat starts, in vertices
+        let mut repeats: Vec<(usize, &Token)> = vec![];
+
+        let mut rate = (1, 1);
+        let mut bhvr = Behavior::GlissMedium;
+
+        let mut tokens = tokens.iter().peekable();
+
+        while let Some(tok) = tokens.next() {
+            match tok.text {
+                ";" => continue,
+                "loop" => {
+                    looped = true;
+                    continue;
+                }
+                "|:" => {
+                    repeats.push((vertices.len(), tok));
+                    continue;
+                }
+                _ => {}
+            }
+
+            if let Some(count) = tok.text.strip_prefix(":|") {
+                let count = match count {
+                    "" => 2,
+                    _ => count
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| tok.error(format!("invalid repeat count '{}'", count)))?,
+                };
+
+                let (start, _) = repeats
+                    .pop()
+                    .ok_or_else(|| tok.error("':|' without a '|:'".to_string()))?;
+
+                let section = vertices[start..].to_vec();
+                let total = section
+                    .len()
+                    .saturating_mul(count - 1)
+                    .saturating_add(vertices.len());
+
+                if total > MAX_VERTICES {
+                    return Err(tok.error(format!(
+                        "path too long after repeats (over {} vertices)",
+                        MAX_VERTICES
+                    )));
+                }
+
+                for _ in 1..count {
+                    vertices.extend_from_slice(&section);
+                }
+
+                continue;
+            }
+
+            // a vertex, starting with its value
+            let val = match tok.text {
+                "r" | "rest" => match vertices.last() {
+                    Some(v) => v.val,
+                    None => return Err(tok.error("rest before any value".to_string())),
+                },
+                s => s
+                    .parse::<f32>()
+                    .ok()
+                    .or_else(|| parse_note(s))
+                    .ok_or_else(|| tok.error(format!("invalid value '{}'", s)))?,
+            };
+
+            let mut have_rate = false;
+            let mut have_bhvr = false;
+
+            while let Some(tok) = tokens.next_if(|t| !t.ends_vertex()) {
+                if let Some(r) = parse_rate(tok.text) {
+                    if have_rate {
+                        return Err(tok.error(format!("more than one rate: '{}'", tok.text)));
+                    }
+                    rate = r;
+                    have_rate = true;
+                } else if let Some(b) = parse_behavior(tok)? {
+                    if have_bhvr {
+                        return Err(tok.error(format!("more than one behavior: '{}'", tok.text)));
+                    }
+                    bhvr = b;
+                    have_bhvr = true;
+                } else if tok.text.contains('/')
+                    || tok.text.starts_with(|c: char| c.is_ascii_digit())
+                {
+                    return Err(tok.error(format!("invalid rate '{}'", tok.text)));
+                } else {
+                    return Err(tok.error(format!("unknown behavior '{}'", tok.text)));
+                }
+            }
+
+            if vertices.len() >= MAX_VERTICES {
+                return Err(tok.error(format!("path too long (over {} vertices)", MAX_VERTICES)));
+            }
+
+            vertices.push(GestureVertex {
+                val,
+                num: rate.0,
+                den: rate.1,
+                bhvr,
+            });
+        }
+
+        if let Some((_, tok)) = repeats.pop() {
+            return Err(tok.error("'|:' is never closed".to_string()));
+        }
+
+        if vertices.is_empty() {
+            return Err(NotationError {
+                line: text.lines().count().max(1),
+                column: 1,
+                msg: "empty path".to_string(),
+            });
+        }
+
+        Ok(GesturePath { vertices, looped })
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    // total length, in clock periods
+    pub fn duration(&self) -> f32 {
+        self.vertices
+            .iter()
+            .map(|v| v.den as f32 / v.num as f32)
+            .sum()
+    }
+
+    // a builder with the whole path, ready to tick
+    pub fn builder(&self) -> LinearGestureBuilder {
+        let mut gst = LinearGestureBuilder::new();
+
+        for vtx in self.vertices.iter() {
+            gst.append(*vtx);
+        }
+
+        gst.set_loop(self.looped);
+        gst.done();
+        gst
+    }
+
+    // Queues the path as events, one vertex per period, which
+    // takes EVENTS_PER_VERTEX events per vertex. Looping is left
+    // to the caller. If the queue doesn't have room for the whole
+    // path, nothing is queued.
+    pub fn enqueue(&self, gst: &mut EventfulGesture) -> Result<(), GestureEventError> {
+        let needed = self.vertices.len() * EVENTS_PER_VERTEX;
+
+        if let Some(remaining) = gst.remaining() {
+            if remaining < needed {
+                return Err(GestureEventError::QueueFull(gst.pending() + remaining));
+            }
+        }
+
+        for vtx in self.vertices.iter() {
+            gst.scalar(vtx.val)?;
+            gst.rate([vtx.num, vtx.den])?;
+            gst.behavior(vtx.bhvr)?;
+            gst.wait(1)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SignalGenerator;
+
+    fn vals(path: &GesturePath) -> Vec<f32> {
+        path.vertices.iter().map(|v| v.val).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "
+            # a comment
+            66 1/2 gm; c4 1/4 lin
+            f#3 exp(-2.5); bb2 3; r gliss(0.5, 2) 2/3 # another
+            loop
+        ";
+
+        let path = GesturePath::parse(text).unwrap();
+
+        assert!(path.looped);
+        assert_eq!(vals(&path), vec![66.0, 60.0, 54.0, 46.0, 46.0]);
+
+        let v = &path.vertices;
+        assert_eq!((v[0].num, v[0].den), (1, 2));
+        assert!(matches!(v[0].bhvr, Behavior::GlissMedium));
+        assert!(matches!(v[1].bhvr, Behavior::Linear));
+        // rate carries over, behavior changes
+        assert_eq!((v[2].num, v[2].den), (1, 4));
+        assert!(matches!(v[2].bhvr, Behavior::Exponential(c) if c == -2.5));
+        assert_eq!((v[3].num, v[3].den), (3, 1));
+        assert!(matches!(v[3].bhvr, Behavior::Exponential(_)));
+        assert_eq!((v[4].num, v[4].den), (2, 3));
+        assert!(matches!(v[4].bhvr, Behavior::Gliss(p, e) if p == 0.5 && e == 2.0));
+
+        assert_eq!(path.duration(), 2.0 + 4.0 + 4.0 + 1.0 / 3.0 + 1.5);
+
+        let mut gst = path.builder();
+        assert_eq!(gst.tick(0.0), 66.0);
+
+        // an empty path made by hand doesn't panic either
+        let empty = GesturePath {
+            vertices: vec![],
+            looped: false,
+        };
+        let mut gst = empty.builder();
+        for clk in [0.0, 0.5, 0.0, 0.5] {
+            assert_eq!(gst.tick(clk), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_repeats() {
+        let path = GesturePath::parse("60 |: 62 |: 64 :|3 65 :| 67").unwrap();
+        assert_eq!(
+            vals(&path),
+            vec![60.0, 62.0, 64.0, 64.0, 64.0, 65.0, 62.0, 64.0, 64.0, 64.0, 65.0, 67.0]
+        );
+        assert!(!path.looped);
+    }
+
+    #[test]
+    fn test_enqueue() {
+        let path = GesturePath::parse("60 1/2 lin; 62 2; 64").unwrap();
+
+        let mut gst = EventfulGesture::new();
+        path.enqueue(&mut gst).unwrap();
+        assert_eq!(gst.pending(), 12);
+
+        // all or nothing, so no half vertex is left behind
+        let mut gst = EventfulGesture::with_capacity(12);
+        gst.scalar(50.0).unwrap();
+        assert_eq!(
+            path.enqueue(&mut gst),
+            Err(GestureEventError::QueueFull(12))
+        );
+        assert_eq!(gst.pending(), 1);
+        assert_eq!(gst.remaining(), Some(11));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |text: &str| GesturePath::parse(text).err().unwrap();
+
+        let e = err("60 1/2 gm;\n  62 1/0");
+        assert_eq!((e.line, e.column), (2, 6));
+        assert_eq!(e.to_string(), "line 2, column 6: invalid rate '1/0'");
+
+        let e = err("60; h4");
+        assert_eq!((e.line, e.column), (1, 5));
+        assert_eq!(e.msg, "invalid value 'h4'");
+
+        assert_eq!(err("60 wobble").msg, "unknown behavior 'wobble'");
+        assert_eq!(err("60 gm lin").msg, "more than one behavior: 'lin'");
+        assert_eq!(
+            err("60 gliss(1)").msg,
+            "wrong number of arguments in 'gliss(1)'"
+        );
+        assert_eq!(err("60 exp(2").msg, "unclosed '(' in 'exp(2'");
+        assert_eq!(err("r 60").msg, "rest before any value");
+        assert_eq!(err("60 :|").msg, "':|' without a '|:'");
+
+        let e = err("60\n |: 62");
+        assert_eq!((e.line, e.column), (2, 2));
+        assert_eq!(e.msg, "'|:' is never closed");
+
+        assert_eq!(err("").msg, "empty path");
+        assert_eq!(err("# just a comment\nloop").msg, "empty path");
+
+        // repeats can't blow up the path
+        let e = err("60 |: 62 :|4000000000");
+        assert_eq!((e.line, e.column), (1, 10));
+        assert!(e.msg.starts_with("path too long"));
+        let e = err("|: |: |: |: 60 :|100 :|100 :|100 :|100");
+        assert_eq!((e.line, e.column), (1, 28));
+    }
+}
@@ -0,0 +1,608 @@
+// OSC (Open Sound Control) receiver, for driving voices from
+// SuperCollider, Pure Data, TouchOSC and the like.
+//
+// OscServer listens on a localhost UDP port in its own thread,
+// and hands the messages over to the rendering loop through a
+// channel. The loop calls apply (once per block is plenty),
+// which maps these addresses onto the voices and the reverb:
+//
+// /voice/N/pitch f             note number
+// /voice/N/bend f              semitones
+// /voice/N/vibrato/rate f      Hz
+// /voice/N/vibrato/depth f     semitones
+// /voice/N/velum f
+// /voice/N/tract/drm f f f f f f f f
+// /voice/N/tract/tongue f f    position, diameter
+// /voice/N/tract/length f      cm
+// /voice/N/tract/turbulence f f  level, position
+// /voice/N/glot/shape f
+// /voice/N/glot/aspiration f
+// /voice/N/glot/noise_floor f
+// /voice/N/glot/pressure f     Pa
+// /voice/N/glot/effort f       0-1
+// /voice/N/glot/whisper f
+// /reverb/size f
+// /reverb/cutoff f             Hz
+//
+// Voices are numbered from 1. N can be an OSC address pattern,
+// matched against the voice numbers: "*" addresses all of them,
+// "{1,3}" the first and third, "[2-4]" the second to fourth, and
+// "?" any of the first nine. Integers are accepted anywhere floats are. Bundles are
+// unpacked, and applied right away (time tags are ignored).
+//
+// Only built with the osc feature: cargo build --features osc
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::net::UdpSocket;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::BigVerb;
+use crate::Glot;
+use crate::Voice;
+
+// how often the receiving thread checks if it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const MAX_PACKET: usize = 65536;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscError {
+    // the packet ended before the message did
+    Truncated,
+    InvalidAddress(String),
+    UnknownTypeTag(char),
+    // address and description
+    BadArguments(String, String),
+    UnknownAddress(String),
+}
+
+impl fmt::Display for OscError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OscError::Truncated => write!(f, "truncated OSC packet"),
+            OscError::InvalidAddress(a) => write!(f, "invalid OSC address '{}'", a),
+            OscError::UnknownTypeTag(t) => write!(f, "unknown OSC type tag '{}'", t),
+            OscError::BadArguments(a, msg) => write!(f, "{}: {}", a, msg),
+            OscError::UnknownAddress(a) => write!(f, "unknown OSC address '{}'", a),
+        }
+    }
+}
+
+impl std::error::Error for OscError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+    Bool(bool),
+}
+
+impl OscArg {
+    // numbers (and booleans) as a float
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            OscArg::Int(i) => Some(*i as f32),
+            OscArg::Float(f) => Some(*f),
+            OscArg::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], OscError> {
+        if self.pos + n > self.data.len() {
+            return Err(OscError::Truncated);
+        }
+
+        let b = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn u32(&mut self) -> Result<u32, OscError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // null terminated, padded to 4 bytes
+    fn string(&mut self) -> Result<String, OscError> {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().position(|&b| b == 0).ok_or(OscError::Truncated)?;
+        let s = String::from_utf8_lossy(&rest[..len]).to_string();
+        self.bytes(pad4(len + 1))?;
+        Ok(s)
+    }
+
+    fn blob(&mut self) -> Result<Vec<u8>, OscError> {
+        let len = self.u32()? as usize;
+        let b = self.bytes(len)?.to_vec();
+        self.bytes(pad4(len) - len)?;
+        Ok(b)
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+fn pad4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+impl OscMessage {
+    pub fn new(address: &str, args: Vec<OscArg>) -> Self {
+        OscMessage {
+            address: address.to_string(),
+            args,
+        }
+    }
+
+    // Parses a packet: a message, or a bundle (possibly
+    // nested), returned as a flat list of messages.
+    pub fn parse(packet: &[u8]) -> Result<Vec<OscMessage>, OscError> {
+        let mut msgs = vec![];
+        parse_packet(packet, &mut msgs)?;
+        Ok(msgs)
+    }
+
+    // encodes the message as an OSC packet
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        write_string(&mut out, &self.address);
+
+        let tags: String = self
+            .args
+            .iter()
+            .map(|a| match a {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+                OscArg::Blob(_) => 'b',
+                OscArg::Bool(true) => 'T',
+                OscArg::Bool(false) => 'F',
+            })
+            .collect();
+
+        write_string(&mut out, &format!(",{}", tags));
+
+        for arg in self.args.iter() {
+            match arg {
+                OscArg::Int(i) => out.extend(i.to_be_bytes()),
+                OscArg::Float(f) => out.extend(f.to_be_bytes()),
+                OscArg::String(s) => write_string(&mut out, s),
+                OscArg::Blob(b) => {
+                    out.extend((b.len() as u32).to_be_bytes());
+                    out.extend(b);
+                    out.resize(pad4(out.len()), 0);
+                }
+                OscArg::Bool(_) => {}
+            }
+        }
+
+        out
+    }
+
+    fn floats(&self, n: usize) -> Result<Vec<f32>, OscError> {
+        let vals: Option<Vec<f32>> = self.args.iter().map(|a| a.as_f32()).collect();
+
+        match vals {
+            Some(v) if v.len() == n => Ok(v),
+            _ => Err(OscError::BadArguments(
+                self.address.clone(),
+                format!("expected {} number(s)", n),
+            )),
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend(s.as_bytes());
+    out.push(0);
+    out.resize(pad4(out.len()), 0);
+}
+
+fn parse_packet(packet: &[u8], msgs: &mut Vec<OscMessage>) -> Result<(), OscError> {
+    let mut r = Reader { data: packet, pos: 0 };
+    let address = r.string()?;
+
+    if address == "#bundle" {
+        // time tag, then size-prefixed elements
+        r.bytes(8)?;
+
+        while !r.done() {
+            let len = r.u32()? as usize;
+            parse_packet(r.bytes(len)?, msgs)?;
+        }
+
+        return Ok(());
+    }
+
+    if !address.starts_with('/') {
+        return Err(OscError::InvalidAddress(address));
+    }
+
+    // some old senders leave out the type tags
+    let tags = if r.done() { ",".to_string() } else { r.string()? };
+    let mut args = vec![];
+
+    for tag in tags.chars().skip(1) {
+        let arg = match tag {
+            'i' => OscArg::Int(r.u32()? as i32),
+            'f' => OscArg::Float(f32::from_bits(r.u32()?)),
+            's' | 'S' => OscArg::String(r.string()?),
+            'b' => OscArg::Blob(r.blob()?),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            _ => return Err(OscError::UnknownTypeTag(tag)),
+        };
+
+        args.push(arg);
+    }
+
+    msgs.push(OscMessage { address, args });
+    Ok(())
+}
+
+// Applies one message to the voices and reverb, see the top
+// of this file for the addresses. The voice number can be an
+// OSC 1.0 address pattern (see pattern_match), and the message
+// goes to every voice it matches.
+pub fn apply_osc(
+    msg: &OscMessage,
+    voices: &mut [Voice<Glot>],
+    reverb: &mut BigVerb
+) -> Result<(), OscError> {
+    let parts: Vec<&str> = msg.address.split('/').skip(1).collect();
+    let unknown = || OscError::UnknownAddress(msg.address.clone());
+
+    match parts.as_slice() {
+        ["reverb", "size"] => reverb.size = msg.floats(1)?[0],
+        ["reverb", "cutoff"] => reverb.cutoff = msg.floats(1)?[0],
+        ["voice", index, param @ ..] => {
+            let mut matched = false;
+
+            for (i, voice) in voices.iter_mut().enumerate() {
+                let number = (i + 1).to_string();
+
+                if pattern_match(index.as_bytes(), number.as_bytes()) {
+                    apply_voice(msg, param, voice)?;
+                    matched = true;
+                }
+            }
+
+            if !matched {
+                return Err(unknown());
+            }
+        }
+        _ => return Err(unknown()),
+    }
+
+    Ok(())
+}
+
+// Matches one part of an address against an OSC 1.0 pattern:
+// "?" is any character, "*" any run of them, "[a-z]" any in the
+// set ("[!a-z]" any not in it), and "{foo,bar}" any of the words.
+fn pattern_match(pat: &[u8], s: &[u8]) -> bool {
+    match pat.first() {
+        None => s.is_empty(),
+        Some(b'*') => (0..=s.len()).any(|i| pattern_match(&pat[1..], &s[i..])),
+        Some(b'?') => !s.is_empty() && pattern_match(&pat[1..], &s[1..]),
+        Some(b'[') => {
+            let end = match pat.iter().position(|&c| c == b']') {
+                Some(end) => end,
+                None => return false,
+            };
+
+            let c = match s.first() {
+                Some(&c) => c,
+                None => return false,
+            };
+
+            let (negate, set) = match &pat[1..end] {
+                [b'!', set @ ..] => (true, set),
+                set => (false, set),
+            };
+
+            // a "-" at either end is just a dash
+            let mut found = false;
+            let mut i = 0;
+
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+
+            found != negate && pattern_match(&pat[end + 1..], &s[1..])
+        }
+        Some(b'{') => {
+            let end = match pat.iter().position(|&c| c == b'}') {
+                Some(end) => end,
+                None => return false,
+            };
+
+            pat[1..end].split(|&c| c == b',').any(|word| {
+                s.starts_with(word) && pattern_match(&pat[end + 1..], &s[word.len()..])
+            })
+        }
+        Some(&c) => s.first() == Some(&c) && pattern_match(&pat[1..], &s[1..]),
+    }
+}
+
+fn apply_voice(msg: &OscMessage, param: &[&str], voice: &mut Voice<Glot>) -> Result<(), OscError> {
+    match param {
+        ["pitch"] => voice.pitch = msg.floats(1)?[0],
+        ["bend"] => voice.bend = msg.floats(1)?[0],
+        ["vibrato", "rate"] => voice.vibrato_rate(msg.floats(1)?[0]),
+        ["vibrato", "depth"] => voice.vibrato_depth(msg.floats(1)?[0]),
+        ["velum"] => voice.nose.set_velum(msg.floats(1)?[0]),
+        ["tract", "drm"] => voice.tract.drm(&msg.floats(8)?),
+        ["tract", "tongue"] => {
+            let v = msg.floats(2)?;
+            voice.tract.tongue_shape(v[0], v[1]);
+        }
+        ["tract", "length"] => voice.set_length(msg.floats(1)?[0]),
+        ["tract", "turbulence"] => {
+            let v = msg.floats(2)?;
+            voice.tract.set_turbulence(v[0], v[1]);
+        }
+        ["glot", "shape"] => voice.glottis.set_shape(msg.floats(1)?[0]),
+        ["glot", "aspiration"] => voice.glottis.set_aspiration(msg.floats(1)?[0]),
+        ["glot", "noise_floor"] => voice.glottis.set_noise_floor(msg.floats(1)?[0]),
+        ["glot", "pressure"] => voice.set_pressure(msg.floats(1)?[0]),
+        ["glot", "effort"] => voice.glottis.set_effort(msg.floats(1)?[0]),
+        ["glot", "whisper"] => voice.set_whisper(msg.floats(1)?[0]),
+        _ => return Err(OscError::UnknownAddress(msg.address.clone())),
+    }
+
+    Ok(())
+}
+
+// Receives OSC messages on a localhost UDP port, in a
+// background thread. The thread stops when the server is
+// dropped.
+pub struct OscServer {
+    rx: mpsc::Receiver<Vec<OscMessage>>,
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl OscServer {
+    // listens on 127.0.0.1. Port 0 picks a free port,
+    // see local_addr.
+    pub fn bind(port: u16) -> io::Result<OscServer> {
+        let socket = UdpSocket::bind(("127.0.0.1", port))?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let addr = socket.local_addr()?;
+        let (tx, rx) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let run = running.clone();
+
+        let thread = thread::spawn(move || {
+            let mut buf = vec![0u8; MAX_PACKET];
+
+            while run.load(Ordering::Relaxed) {
+                let len = match socket.recv(&mut buf) {
+                    Ok(len) => len,
+                    // timeouts, just check if we should stop
+                    Err(_) => continue,
+                };
+
+                // malformed packets are dropped
+                if let Ok(msgs) = OscMessage::parse(&buf[..len]) {
+                    if tx.send(msgs).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(OscServer {
+            rx,
+            addr,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // messages received since the last call, without blocking
+    pub fn poll(&self) -> Vec<OscMessage> {
+        self.rx.try_iter().flatten().collect()
+    }
+
+    // Applies the messages received since the last call.
+    // Messages that don't map onto anything are returned
+    // as errors, the rest are still applied.
+    pub fn apply(&self, voices: &mut [Voice<Glot>], reverb: &mut BigVerb) -> Vec<OscError> {
+        self.poll()
+            .iter()
+            .filter_map(|msg| apply_osc(msg, voices, reverb).err())
+            .collect()
+    }
+}
+
+impl Drop for OscServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_parse() {
+        let msg = OscMessage::new("/voice/1/tract/tongue", vec![
+            OscArg::Float(0.5),
+            OscArg::Int(1),
+            OscArg::String("abcd".to_string()),
+            OscArg::Blob(vec![1, 2, 3]),
+            OscArg::Bool(true),
+        ]);
+
+        let bytes = msg.to_bytes();
+        assert_eq!(bytes.len() % 4, 0);
+        assert_eq!(OscMessage::parse(&bytes), Ok(vec![msg.clone()]));
+
+        // a bundle with a message and a nested bundle
+        let other = OscMessage::new("/reverb/size", vec![OscArg::Float(0.8)]);
+        let mut inner = b"#bundle\0".to_vec();
+        inner.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        inner.extend((other.to_bytes().len() as u32).to_be_bytes());
+        inner.extend(other.to_bytes());
+
+        let mut bundle = b"#bundle\0".to_vec();
+        bundle.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        bundle.extend((bytes.len() as u32).to_be_bytes());
+        bundle.extend(&bytes);
+        bundle.extend((inner.len() as u32).to_be_bytes());
+        bundle.extend(inner);
+
+        assert_eq!(OscMessage::parse(&bundle), Ok(vec![msg, other]));
+
+        assert_eq!(OscMessage::parse(&bytes[..bytes.len() - 4]), Err(OscError::Truncated));
+        assert_eq!(
+            OscMessage::parse(b"/a\0\0,x\0\0"),
+            Err(OscError::UnknownTypeTag('x'))
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let sr = 44100;
+        let mut voices = vec![Voice::new(sr, 17.0, 1), Voice::new(sr, 17.0, 1)];
+        let mut reverb = BigVerb::new(sr);
+
+        let msgs = [
+            OscMessage::new("/voice/*/pitch", vec![OscArg::Int(62)]),
+            OscMessage::new("/voice/2/bend", vec![OscArg::Float(-1.5)]),
+            OscMessage::new("/reverb/cutoff", vec![OscArg::Float(4000.0)]),
+        ];
+
+        for msg in msgs.iter() {
+            apply_osc(msg, &mut voices, &mut reverb).unwrap();
+        }
+
+        assert_eq!(voices[0].pitch, 62.0);
+        assert_eq!(voices[1].pitch, 62.0);
+        assert_eq!(voices[0].bend, 0.0);
+        assert_eq!(voices[1].bend, -1.5);
+        assert_eq!(reverb.cutoff, 4000.0);
+
+        let bad = OscMessage::new("/voice/3/pitch", vec![OscArg::Float(60.0)]);
+        assert!(matches!(
+            apply_osc(&bad, &mut voices, &mut reverb),
+            Err(OscError::UnknownAddress(_))
+        ));
+
+        let bad = OscMessage::new("/voice/[3-9]/pitch", vec![OscArg::Float(60.0)]);
+        assert!(matches!(
+            apply_osc(&bad, &mut voices, &mut reverb),
+            Err(OscError::UnknownAddress(_))
+        ));
+
+        let bad = OscMessage::new("/voice/1/tract/drm", vec![OscArg::Float(1.0)]);
+        assert!(matches!(
+            apply_osc(&bad, &mut voices, &mut reverb),
+            Err(OscError::BadArguments(..))
+        ));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let sr = 44100;
+        let mut voices: Vec<_> = (0..12).map(|_| Voice::new(sr, 17.0, 1)).collect();
+        let mut reverb = BigVerb::new(sr);
+
+        // numbers of the voices a message went to
+        let pitches = |voices: &[Voice<Glot>]| -> Vec<usize> {
+            (1..=voices.len()).filter(|&n| voices[n - 1].pitch == 1.0).collect()
+        };
+
+        let cases: [(&str, Vec<usize>); 7] = [
+            ("{1,3}", vec![1, 3]),
+            ("[2-4]", vec![2, 3, 4]),
+            ("?", (1..=9).collect()),
+            ("1?", vec![10, 11, 12]),
+            ("[!1-8]", vec![9]),
+            ("1*", vec![1, 10, 11, 12]),
+            ("{12,2}", vec![2, 12]),
+        ];
+
+        for (pattern, expected) in cases {
+            for voice in voices.iter_mut() {
+                voice.pitch = 0.0;
+            }
+
+            let address = format!("/voice/{}/pitch", pattern);
+            let msg = OscMessage::new(&address, vec![OscArg::Float(1.0)]);
+            apply_osc(&msg, &mut voices, &mut reverb).unwrap();
+            assert_eq!(pitches(&voices), expected, "{}", pattern);
+        }
+
+        assert!(pattern_match(b"a-z", b"a-z"));
+        assert!(pattern_match(b"[-a]", b"-"));
+        assert!(!pattern_match(b"[1-3", b"1"));
+        assert!(!pattern_match(b"{1,2", b"1"));
+    }
+
+    #[test]
+    fn test_udp() {
+        let sr = 44100;
+        let server = OscServer::bind(0).unwrap();
+        let mut voices = vec![Voice::new(sr, 17.0, 1)];
+        let mut reverb = BigVerb::new(sr);
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let msg = OscMessage::new("/voice/1/pitch", vec![OscArg::Float(67.0)]);
+        socket.send_to(&msg.to_bytes(), server.local_addr()).unwrap();
+        socket.send_to(b"garbage", server.local_addr()).unwrap();
+
+        let start = Instant::now();
+
+        while voices[0].pitch != 67.0 && start.elapsed() < Duration::from_secs(5) {
+            let errors = server.apply(&mut voices, &mut reverb);
+            assert!(errors.is_empty());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(voices[0].pitch, 67.0);
+    }
+}
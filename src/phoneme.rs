@@ -0,0 +1,678 @@
+// Phoneme sequencer, for making Voice sing lyrics.
+//
+// Phonemes are written as ARPAbet (as used by the CMU
+// pronouncing dictionary) or IPA symbols, each with an optional
+// duration in seconds:
+//
+// HH:0.08 AH:0.3 L:0.08 OW:0.6 _:0.3
+// h:0.08 ʌ:0.3 l:0.08 oʊ:0.6
+//
+// ARPAbet is case insensitive, and stress markers (AH0, AH1)
+// are ignored. "_" (or SIL, SP, PAU) is a pause.
+//
+// Each phoneme is a set of articulatory targets: a tract shape
+// (DRM regions), velum opening, voicing, frication noise and
+// loudness. Consonants only specify the regions they constrict;
+// the rest are "don't care" and are inherited from the
+// neighbouring vowels, which is where most coarticulation comes
+// from. Targets are then crossfaded around each boundary.
+
+use std::fmt;
+use std::sync::OnceLock;
+use crate::Glot;
+use crate::Voice;
+
+// Tract treats an area of exactly 0 as open, so
+// closures use a very small area instead
+const CLOSED: f32 = 0.001;
+
+// schwa-like shape, used when there is no vowel around
+const NEUTRAL: [f32; 8] = [1.2, 1.2, 1.4, 1.4, 1.4, 1.4, 1.4, 1.6];
+
+const VELUM_OPEN: f32 = 0.4;
+
+// where frication noise enters the tract (0 is the glottis,
+// 1 the lips), just in front of the constriction
+const LABIAL: f32 = 0.98;
+const DENTAL: f32 = 0.95;
+const ALVEOLAR: f32 = 0.92;
+const POSTALVEOLAR: f32 = 0.85;
+const VELAR: f32 = 0.7;
+
+// noise burst on the release of a stop
+const BURST_TIME: f32 = 0.015;
+const BURST_LEVEL: f32 = 0.5;
+
+// stops are released quickly, whatever the coarticulation time
+const STOP_RELEASE: f32 = 0.01;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PhonemeError {
+    // column (1-indexed) and the offending text
+    UnknownPhoneme(usize, String),
+    InvalidDuration(usize, String),
+}
+
+impl fmt::Display for PhonemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhonemeError::UnknownPhoneme(col, s) => {
+                write!(f, "column {}: unknown phoneme '{}'", col, s)
+            }
+            PhonemeError::InvalidDuration(col, s) => {
+                write!(f, "column {}: invalid duration '{}'", col, s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PhonemeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhonemeKind {
+    Vowel,
+    Stop,
+    Nasal,
+    Fricative,
+    Approximant,
+    Aspirate,
+    Silence,
+    // diphthongs and affricates, made of other phonemes
+    Compound,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Phoneme {
+    pub arpabet: &'static str,
+    pub ipa: &'static str,
+    pub kind: PhonemeKind,
+
+    // DRM regions. None is "don't care".
+    pub drm: [Option<f32>; 8],
+    pub velum: f32,
+    pub voiced: bool,
+
+    // frication noise level, and where it enters the tract
+    pub turbulence: f32,
+    pub place: f32,
+
+    // glottal noise when unvoiced (as in /h/)
+    pub aspiration: f32,
+
+    // relative loudness of the voicing
+    pub amplitude: f32,
+
+    // default duration, in seconds
+    pub duration: f32,
+
+    // for compounds: the parts (by ARPAbet name) and
+    // their share of the duration
+    pub parts: &'static [(&'static str, f32)],
+}
+
+impl Phoneme {
+    const fn new(arpabet: &'static str, ipa: &'static str, kind: PhonemeKind) -> Self {
+        Phoneme {
+            arpabet,
+            ipa,
+            kind,
+            drm: [None; 8],
+            velum: 0.0,
+            voiced: true,
+            turbulence: 0.0,
+            place: 0.0,
+            aspiration: 0.0,
+            amplitude: 1.0,
+            duration: 0.08,
+            parts: &[],
+        }
+    }
+
+    const fn vowel(arpabet: &'static str, ipa: &'static str, d: [f32; 8]) -> Self {
+        let mut p = Phoneme::new(arpabet, ipa, PhonemeKind::Vowel);
+        p.drm = [
+            Some(d[0]), Some(d[1]), Some(d[2]), Some(d[3]),
+            Some(d[4]), Some(d[5]), Some(d[6]), Some(d[7]),
+        ];
+        p.duration = 0.22;
+        p
+    }
+
+    const fn compound(
+        arpabet: &'static str,
+        ipa: &'static str,
+        duration: f32,
+        parts: &'static [(&'static str, f32)]
+    ) -> Self {
+        let mut p = Phoneme::new(arpabet, ipa, PhonemeKind::Compound);
+        p.duration = duration;
+        p.parts = parts;
+        p
+    }
+
+    const fn region(mut self, region: usize, area: f32) -> Self {
+        self.drm[region] = Some(area);
+        self
+    }
+
+    const fn unvoiced(mut self) -> Self {
+        self.voiced = false;
+        self
+    }
+
+    const fn noise(mut self, level: f32, place: f32) -> Self {
+        self.turbulence = level;
+        self.place = place;
+        self
+    }
+
+    const fn nasal(mut self) -> Self {
+        self.velum = VELUM_OPEN;
+        self
+    }
+
+    const fn amp(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    const fn aspirated(mut self, level: f32) -> Self {
+        self.aspiration = level;
+        self
+    }
+
+    const fn dur(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    // finds a phoneme by ARPAbet (case insensitive, stress
+    // markers ignored) or IPA symbol
+    pub fn lookup(symbol: &str) -> Option<&'static Phoneme> {
+        let upper = symbol.to_uppercase();
+        let arpabet = upper.trim_end_matches(|c: char| c.is_ascii_digit());
+
+        PHONEMES
+            .iter()
+            .find(|p| p.arpabet == arpabet)
+            .or_else(|| PHONEMES.iter().find(|p| !p.ipa.is_empty() && p.ipa == symbol))
+            .or_else(|| SILENCES.contains(&arpabet).then_some(&PHONEMES[0]))
+    }
+}
+
+use PhonemeKind::*;
+
+const SILENCES: [&str; 3] = ["SIL", "SP", "PAU"];
+
+static PHONEMES: [Phoneme; 41] = [
+    Phoneme::new("_", "", Silence).unvoiced().amp(0.0).dur(0.2),
+
+    // vowels (approximate shapes)
+    Phoneme::vowel("AA", "ɑ", [0.4, 0.3, 0.3, 0.6, 1.5, 3.0, 3.2, 2.4]),
+    Phoneme::vowel("AE", "æ", [0.6, 0.5, 0.6, 1.0, 1.8, 2.6, 2.8, 2.8]),
+    Phoneme::vowel("AH", "ʌ", [0.8, 0.6, 0.7, 1.2, 1.6, 2.2, 2.2, 2.0]),
+    Phoneme::vowel("AX", "ə", [1.2, 1.2, 1.4, 1.4, 1.4, 1.4, 1.4, 1.6]),
+    Phoneme::vowel("AO", "ɔ", [0.5, 0.3, 0.4, 0.8, 1.6, 3.0, 2.0, 1.2]),
+    Phoneme::vowel("EH", "ɛ", [1.4, 1.6, 1.8, 2.0, 1.4, 1.2, 1.8, 2.2]),
+    Phoneme::vowel("ER", "ɝ", [1.2, 1.0, 1.4, 1.0, 0.6, 1.6, 1.4, 1.2]),
+    Phoneme::vowel("IH", "ɪ", [2.0, 2.2, 2.8, 2.6, 1.0, 0.7, 1.2, 2.0]),
+    Phoneme::vowel("IY", "i", [2.4, 3.0, 4.0, 3.7, 0.6, 0.3, 0.6, 1.8]),
+    Phoneme::vowel("UH", "ʊ", [1.6, 1.8, 2.0, 1.4, 0.6, 1.2, 1.4, 0.9]),
+    Phoneme::vowel("UW", "u", [2.0, 2.4, 2.6, 1.6, 0.3, 1.4, 1.6, 0.4]),
+    Phoneme::vowel("OW", "oʊ", [1.0, 0.8, 1.0, 1.4, 0.9, 2.2, 1.8, 0.8]),
+    Phoneme::vowel("EY", "e", [1.8, 2.0, 2.4, 2.4, 1.2, 0.9, 1.5, 2.1]),
+
+    // diphthongs
+    Phoneme::compound("AY", "aɪ", 0.3, &[("AA", 0.6), ("IH", 0.4)]),
+    Phoneme::compound("AW", "aʊ", 0.3, &[("AA", 0.6), ("UH", 0.4)]),
+    Phoneme::compound("OY", "ɔɪ", 0.3, &[("AO", 0.6), ("IH", 0.4)]),
+
+    // stops
+    Phoneme::new("P", "p", Stop).region(7, CLOSED).unvoiced().noise(0.0, LABIAL),
+    Phoneme::new("B", "b", Stop).region(7, CLOSED).amp(0.2).noise(0.0, LABIAL),
+    Phoneme::new("T", "t", Stop).region(6, CLOSED).unvoiced().noise(0.0, ALVEOLAR),
+    Phoneme::new("D", "d", Stop).region(6, CLOSED).amp(0.2).noise(0.0, ALVEOLAR),
+    Phoneme::new("K", "k", Stop).region(4, CLOSED).unvoiced().noise(0.0, VELAR),
+    Phoneme::new("G", "ɡ", Stop).region(4, CLOSED).amp(0.2).noise(0.0, VELAR),
+
+    // nasals
+    Phoneme::new("M", "m", Nasal).region(7, CLOSED).nasal().amp(0.6),
+    Phoneme::new("N", "n", Nasal).region(6, CLOSED).nasal().amp(0.6),
+    Phoneme::new("NG", "ŋ", Nasal).region(4, CLOSED).nasal().amp(0.6),
+
+    // fricatives
+    Phoneme::new("F", "f", Fricative).region(7, 0.1).unvoiced().noise(0.3, LABIAL)
+        .aspirated(0.03).dur(0.1),
+    Phoneme::new("V", "v", Fricative).region(7, 0.1).noise(0.15, LABIAL).amp(0.5).dur(0.08),
+    Phoneme::new("TH", "θ", Fricative).region(6, 0.15).unvoiced().noise(0.3, DENTAL)
+        .aspirated(0.03).dur(0.1),
+    Phoneme::new("DH", "ð", Fricative).region(6, 0.15).noise(0.12, DENTAL).amp(0.5),
+    Phoneme::new("S", "s", Fricative).region(6, 0.08).unvoiced().noise(0.6, ALVEOLAR)
+        .aspirated(0.03).dur(0.12),
+    Phoneme::new("Z", "z", Fricative).region(6, 0.08).noise(0.3, ALVEOLAR).amp(0.5).dur(0.1),
+    Phoneme::new("SH", "ʃ", Fricative).region(5, 0.12).region(7, 1.0).unvoiced()
+        .noise(0.5, POSTALVEOLAR).aspirated(0.03).dur(0.12),
+    Phoneme::new("ZH", "ʒ", Fricative).region(5, 0.12).region(7, 1.0)
+        .noise(0.25, POSTALVEOLAR).amp(0.5).dur(0.1),
+
+    // affricates
+    Phoneme::compound("CH", "tʃ", 0.14, &[("T", 0.4), ("SH", 0.6)]),
+    Phoneme::compound("JH", "dʒ", 0.12, &[("D", 0.4), ("ZH", 0.6)]),
+
+    // approximants
+    Phoneme::new("L", "l", Approximant).region(6, 0.5).amp(0.8),
+    Phoneme::new("R", "ɹ", Approximant).region(5, 0.6).region(7, 1.0).amp(0.8),
+    Phoneme::new("W", "w", Approximant).region(4, 0.8).region(7, 0.3).amp(0.8),
+    Phoneme::new("Y", "j", Approximant).region(5, 0.4).amp(0.8),
+
+    Phoneme::new("HH", "h", Aspirate).unvoiced().aspirated(0.4),
+];
+
+// Articulatory parameters at one point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArticulatoryState {
+    pub drm: [f32; 8],
+    pub velum: f32,
+    // 0 (unvoiced) to 1 (voiced)
+    pub voicing: f32,
+    pub turbulence: f32,
+    pub place: f32,
+    pub aspiration: f32,
+    pub amplitude: f32,
+}
+
+impl ArticulatoryState {
+    pub fn lerp(&self, other: &ArticulatoryState, alpha: f32) -> ArticulatoryState {
+        let mix = |a: f32, b: f32| a + (b - a) * alpha;
+        let mut drm = [0.0; 8];
+
+        for (i, d) in drm.iter_mut().enumerate() {
+            *d = mix(self.drm[i], other.drm[i]);
+        }
+
+        // the noise stays where the louder source is
+        let place = if self.turbulence >= other.turbulence {
+            self.place
+        } else {
+            other.place
+        };
+
+        ArticulatoryState {
+            drm,
+            velum: mix(self.velum, other.velum),
+            voicing: mix(self.voicing, other.voicing),
+            turbulence: mix(self.turbulence, other.turbulence),
+            place,
+            aspiration: mix(self.aspiration, other.aspiration),
+            amplitude: mix(self.amplitude, other.amplitude),
+        }
+    }
+
+    // Sets up the voice: tract shape, velum, frication, and
+    // voicing (unvoiced sounds use the whisper mode of Glot).
+    // This sets the glottal amplitude, which pressure
+    // control (set_effort) then scales.
+    pub fn apply(&self, voice: &mut Voice<Glot>) {
+        voice.tract.drm(&self.drm);
+        voice.tract.set_turbulence(self.turbulence, self.place);
+        voice.nose.set_velum(self.velum);
+        voice.glottis.set_whisper(1.0 - self.voicing);
+        voice.glottis.set_whisper_level(self.aspiration);
+        voice.glottis.set_amplitude(self.amplitude);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PhonemeSegment {
+    pub phoneme: &'static Phoneme,
+    pub start: f32,
+    pub duration: f32,
+    // targets, with the "don't care" regions filled in
+    pub target: ArticulatoryState,
+}
+
+// the state of a lone silence, which empty timelines hold.
+// Made once, as state_at can be called every sample.
+fn neutral_state() -> ArticulatoryState {
+    static NEUTRAL: OnceLock<ArticulatoryState> = OnceLock::new();
+
+    *NEUTRAL.get_or_init(|| {
+        let silence = Phoneme::lookup("_").unwrap();
+        PhonemeTimeline::from_phonemes(&[(silence, 1.0)]).segments[0].target
+    })
+}
+
+// A sequence of phonemes laid out in time
+#[derive(Debug, Clone)]
+pub struct PhonemeTimeline {
+    segments: Vec<PhonemeSegment>,
+    coarticulation: f32,
+}
+
+impl PhonemeTimeline {
+    pub fn parse(text: &str) -> Result<Self, PhonemeError> {
+        let mut phonemes = vec![];
+
+        for token in text.split_whitespace() {
+            let offset = token.as_ptr() as usize - text.as_ptr() as usize;
+            let column = text[..offset].chars().count() + 1;
+
+            let (symbol, dur) = match token.rsplit_once(':') {
+                Some((s, d)) => (s, Some(d)),
+                None => (token, None),
+            };
+
+            let phoneme = Phoneme::lookup(symbol)
+                .ok_or(PhonemeError::UnknownPhoneme(column, symbol.to_string()))?;
+
+            let dur = match dur {
+                Some(d) => {
+                    let bad = || PhonemeError::InvalidDuration(column, d.to_string());
+                    let d = d.parse::<f32>().map_err(|_| bad())?;
+
+                    if d.is_nan() || d <= 0.0 {
+                        return Err(bad());
+                    }
+
+                    d
+                }
+                None => phoneme.duration,
+            };
+
+            phonemes.push((phoneme, dur));
+        }
+
+        Ok(PhonemeTimeline::from_phonemes(&phonemes))
+    }
+
+    pub fn from_phonemes(phonemes: &[(&'static Phoneme, f32)]) -> Self {
+        let mut expanded = vec![];
+
+        for &(p, dur) in phonemes {
+            if p.parts.is_empty() {
+                expanded.push((p, dur));
+                continue;
+            }
+
+            for &(part, share) in p.parts {
+                if let Some(part) = Phoneme::lookup(part) {
+                    expanded.push((part, dur * share));
+                }
+            }
+        }
+
+        let mut segments = vec![];
+        let mut start = 0.0;
+
+        for (i, &(phoneme, duration)) in expanded.iter().enumerate() {
+            let mut drm = [0.0; 8];
+
+            for (r, d) in drm.iter_mut().enumerate() {
+                *d = match phoneme.drm[r] {
+                    Some(area) => area,
+                    None => inherit(&expanded, i, r),
+                };
+            }
+
+            let target = ArticulatoryState {
+                drm,
+                velum: phoneme.velum,
+                voicing: if phoneme.voiced { 1.0 } else { 0.0 },
+                turbulence: phoneme.turbulence,
+                place: phoneme.place,
+                aspiration: phoneme.aspiration,
+                amplitude: phoneme.amplitude,
+            };
+
+            segments.push(PhonemeSegment {
+                phoneme,
+                start,
+                duration,
+                target,
+            });
+
+            start += duration;
+        }
+
+        PhonemeTimeline {
+            segments,
+            coarticulation: 0.06,
+        }
+    }
+
+    // width (in seconds) of the crossfade between phonemes
+    pub fn set_coarticulation(&mut self, time: f32) {
+        self.coarticulation = time.max(0.0);
+    }
+
+    // Lengthens the last vowel (or the last phoneme, if there
+    // are no vowels) so the timeline lasts at least duration.
+    // Used to fit a syllable to the note it is sung on.
+    pub fn stretch_to(&mut self, duration: f32) {
+        let extra = duration - self.duration();
+
+        if extra <= 0.0 || self.segments.is_empty() {
+            return;
+        }
+
+        let last = self.segments.len() - 1;
+        let i = self
+            .segments
+            .iter()
+            .rposition(|s| s.phoneme.kind == Vowel)
+            .unwrap_or(last);
+
+        self.segments[i].duration += extra;
+
+        for s in self.segments[i + 1..].iter_mut() {
+            s.start += extra;
+        }
+    }
+
+    pub fn segments(&self) -> &[PhonemeSegment] {
+        &self.segments
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.segments
+            .last()
+            .map(|s| s.start + s.duration)
+            .unwrap_or(0.0)
+    }
+
+    // crossfade width at the boundary between segments i-1 and i
+    fn blend_width(&self, i: usize) -> f32 {
+        let a = &self.segments[i - 1];
+        let b = &self.segments[i];
+        let mut w = self.coarticulation.min(0.5 * a.duration).min(0.5 * b.duration);
+
+        if a.phoneme.kind == Stop {
+            w = w.min(STOP_RELEASE);
+        }
+
+        w
+    }
+
+    pub fn state_at(&self, time: f32) -> ArticulatoryState {
+        let segs = &self.segments;
+
+        if segs.is_empty() {
+            return neutral_state();
+        }
+
+        let i = segs
+            .iter()
+            .position(|s| time < s.start + s.duration)
+            .unwrap_or(segs.len() - 1);
+
+        let seg = &segs[i];
+        let mut state = seg.target;
+
+        if i > 0 {
+            let w = self.blend_width(i);
+            let t = time - seg.start;
+
+            if t < 0.5 * w {
+                let alpha = smoothstep((t + 0.5 * w) / w);
+                state = segs[i - 1].target.lerp(&seg.target, alpha);
+            }
+
+            let prev = segs[i - 1].phoneme;
+            if prev.kind == Stop && (0.0..BURST_TIME).contains(&t) {
+                state.turbulence = BURST_LEVEL * (1.0 - t / BURST_TIME);
+                state.place = prev.place;
+            }
+        }
+
+        if i + 1 < segs.len() {
+            let w = self.blend_width(i + 1);
+            let t = seg.start + seg.duration - time;
+
+            if t < 0.5 * w {
+                let alpha = smoothstep((0.5 * w - t) / w);
+                state = seg.target.lerp(&segs[i + 1].target, alpha);
+            }
+        }
+
+        state
+    }
+}
+
+// value of a "don't care" region for phoneme i: from the
+// next vowel, else the previous one, without crossing a pause
+fn inherit(phonemes: &[(&'static Phoneme, f32)], i: usize, region: usize) -> f32 {
+    let vowel = |p: &&(&'static Phoneme, f32)| p.0.kind == Vowel;
+    let pause = |p: &&(&'static Phoneme, f32)| p.0.kind == Silence;
+
+    let next = phonemes[i + 1..]
+        .iter()
+        .take_while(|p| !pause(p))
+        .find(vowel);
+
+    let prev = phonemes[..i]
+        .iter()
+        .rev()
+        .take_while(|p| !pause(p))
+        .find(vowel);
+
+    next.or(prev)
+        .and_then(|p| p.0.drm[region])
+        .unwrap_or(NEUTRAL[region])
+}
+
+fn smoothstep(x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+// Plays a PhonemeTimeline on a Voice, one sample at a time.
+// Pitch is left alone, so it can come from somewhere else
+// (a gesture, a score).
+pub struct PhonemeSequencer {
+    timeline: PhonemeTimeline,
+    onedsr: f32,
+    time: f32,
+}
+
+impl PhonemeSequencer {
+    pub fn new(sr: usize, timeline: PhonemeTimeline) -> Self {
+        PhonemeSequencer {
+            timeline,
+            onedsr: 1.0 / sr as f32,
+            time: 0.0,
+        }
+    }
+
+    pub fn timeline(&mut self) -> &mut PhonemeTimeline {
+        &mut self.timeline
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn reset(&mut self) {
+        self.time = 0.0;
+    }
+
+    pub fn done(&self) -> bool {
+        self.time >= self.timeline.duration()
+    }
+
+    // current articulation, then moves one sample forward
+    pub fn tick_state(&mut self) -> ArticulatoryState {
+        let state = self.timeline.state_at(self.time);
+        self.time += self.onedsr;
+        state
+    }
+
+    // applies the current articulation to the voice and
+    // computes its next sample
+    pub fn tick(&mut self, voice: &mut Voice<Glot>) -> f32 {
+        self.tick_state().apply(voice);
+        voice.tick()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let tl = PhonemeTimeline::parse("HH:0.1 ah1:0.3  l OW:0.5 _").unwrap();
+        let names: Vec<&str> = tl.segments().iter().map(|s| s.phoneme.arpabet).collect();
+        assert_eq!(names, ["HH", "AH", "L", "OW", "_"]);
+        // consonants default to 80ms
+        assert!((tl.segments()[2].duration - 0.08).abs() < 1e-6);
+        assert!((tl.duration() - (0.1 + 0.3 + 0.08 + 0.5 + 0.2)).abs() < 1e-5);
+
+        let ipa = PhonemeTimeline::parse("h:0.1 ʌ:0.3 l oʊ:0.5").unwrap();
+        let names: Vec<&str> = ipa.segments().iter().map(|s| s.phoneme.arpabet).collect();
+        assert_eq!(names, ["HH", "AH", "L", "OW"]);
+
+        // compounds are split into their parts
+        let tl = PhonemeTimeline::parse("AY:0.5 CH:0.2").unwrap();
+        let names: Vec<&str> = tl.segments().iter().map(|s| s.phoneme.arpabet).collect();
+        assert_eq!(names, ["AA", "IH", "T", "SH"]);
+        assert!((tl.duration() - 0.7).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            PhonemeTimeline::parse("AH:0.2 XX:0.1").unwrap_err(),
+            PhonemeError::UnknownPhoneme(8, "XX".to_string())
+        );
+        assert_eq!(
+            PhonemeTimeline::parse("ə:0.2 s:fast").unwrap_err(),
+            PhonemeError::InvalidDuration(7, "fast".to_string())
+        );
+        assert!(PhonemeTimeline::parse("AH:-1").is_err());
+    }
+
+    #[test]
+    fn test_coarticulation() {
+        let tl = PhonemeTimeline::parse("IY:0.3 P:0.1 AA:0.3").unwrap();
+        let iy = Phoneme::lookup("IY").unwrap();
+        let aa = Phoneme::lookup("AA").unwrap();
+
+        // the lips close for P, the tongue is already on its
+        // way to the next vowel
+        let p = tl.segments()[1].target;
+        assert_eq!(p.drm[7], CLOSED);
+        assert_eq!(p.drm[5], aa.drm[5].unwrap());
+
+        // middle of a vowel is on target
+        assert_eq!(tl.state_at(0.15).drm[5], iy.drm[5].unwrap());
+
+        // crossfade at the boundary
+        let mid = tl.state_at(0.3).drm[5];
+        let (a, b) = (iy.drm[5].unwrap(), aa.drm[5].unwrap());
+        assert!(mid > a.min(b) && mid < a.max(b));
+
+        // release burst
+        assert!(tl.state_at(0.405).turbulence > 0.0);
+        assert_eq!(tl.state_at(0.55).turbulence, 0.0);
+    }
+}
@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+// Pitch transitions between notes, timed in milliseconds
+// rather than in gesture clock periods.
+//
+// A glide follows a smooth S curve from one note to the next.
+// Like a singer, it can first dip slightly away from the target
+// (preparation), and overshoot it before settling (over the
+// same time again). Both are given as a fraction of the interval.
+pub struct Portamento {
+    sr: f32,
+    // glide time, in samples
+    time: f32,
+    overshoot: f32,
+    preparation: f32,
+
+    from: f32,
+    to: f32,
+    // samples into the current transition
+    pos: f32,
+    active: bool,
+}
+
+impl Portamento {
+    pub fn new(sr: usize) -> Self {
+        Portamento {
+            sr: sr as f32,
+            time: 0.0,
+            overshoot: 0.0,
+            preparation: 0.0,
+            from: 0.0,
+            to: 0.0,
+            pos: 0.0,
+            active: false,
+        }
+    }
+
+    // glide time, in milliseconds. 0 jumps straight to the note.
+    pub fn set_time(&mut self, ms: f32) {
+        self.time = (ms.max(0.0) * 0.001 * self.sr).round();
+    }
+
+    pub fn set_overshoot(&mut self, amount: f32) {
+        self.overshoot = amount.clamp(0.0, 1.0);
+    }
+
+    pub fn set_preparation(&mut self, amount: f32) {
+        self.preparation = amount.clamp(0.0, 1.0);
+    }
+
+    // starts a transition. A new one can start in the middle
+    // of another, from wherever the pitch is.
+    pub fn glide(&mut self, from: f32, to: f32) {
+        self.from = from;
+        self.to = to;
+        self.pos = 0.0;
+        self.active = self.time > 0.0 && from != to;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn target(&self) -> f32 {
+        self.to
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+
+    // pitch for this sample, or None when not gliding
+    pub fn tick(&mut self) -> Option<f32> {
+        if !self.active {
+            return None;
+        }
+
+        let interval = self.to - self.from;
+        let t = self.pos / self.time;
+        self.pos += 1.0;
+
+        let end = if self.overshoot > 0.0 { 2.0 } else { 1.0 };
+
+        if t >= end {
+            self.active = false;
+            return Some(self.to);
+        }
+
+        let shape = if t < 1.0 {
+            // S curve, with a dip the other way early on
+            let glide = t * t * (3.0 - 2.0 * t);
+            let dip = (PI * t).sin() * (1.0 - t) * (1.0 - t);
+            glide - self.preparation * dip
+        } else {
+            // overshoot, settling back onto the note
+            let s = t - 1.0;
+            1.0 + self.overshoot * (PI * s).sin() * (1.0 - s)
+        };
+
+        Some(self.from + interval * shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glide() {
+        let sr = 1000;
+        let mut p = Portamento::new(sr);
+        p.set_time(100.0);
+        p.glide(60.0, 67.0);
+
+        let out: Vec<f32> = (0..150).map_while(|_| p.tick()).collect();
+        assert_eq!(out.len(), 101);
+        assert_eq!(out[0], 60.0);
+        assert!((out[50] - 63.5).abs() < 1e-3);
+        assert_eq!(out[100], 67.0);
+        assert!(out.windows(2).all(|w| w[1] >= w[0]));
+
+        // with preparation and overshoot, it goes the other way
+        // first, then past the note, then ends on it
+        p.set_preparation(0.2);
+        p.set_overshoot(0.2);
+        p.glide(67.0, 60.0);
+
+        let out: Vec<f32> = (0..300).map_while(|_| p.tick()).collect();
+        assert_eq!(out.len(), 201);
+        assert!(out[..50].iter().any(|&x| x > 67.0));
+        assert!(out[100..].iter().any(|&x| x < 60.0));
+        assert_eq!(out[200], 60.0);
+
+        // no time, no glide
+        p.set_time(0.0);
+        p.glide(60.0, 72.0);
+        assert_eq!(p.tick(), None);
+    }
+}
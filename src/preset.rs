@@ -0,0 +1,474 @@
+// Voice presets and a builder for Voice.
+//
+// A VoicePreset holds everything needed to set up a Voice:
+// tract and nose length, oversampling, glottal parameters,
+// vibrato, a starting pitch and a default vowel (as DRM
+// regions). Presets are provided for the usual voice types,
+// and can be tweaked through VoiceBuilder, or saved and
+// loaded as plain "key = value" text:
+//
+// tract_length = 16
+// vibrato_depth = 0.2
+// vowel = 0.225 0.059 0.059 0.082 0.701 3.701 1.725 1.082
+//
+// Keys left out of the text keep their default values.
+
+use std::fmt;
+use std::str::FromStr;
+use crate::Glot;
+use crate::Voice;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetError {
+    // line number (1-indexed) and description
+    Parse(usize, String),
+    UnknownVoiceType(String),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresetError::Parse(line, msg) => write!(f, "line {}: {}", line, msg),
+            PresetError::UnknownVoiceType(name) => {
+                write!(f, "unknown voice type '{}'", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceType {
+    Soprano,
+    MezzoSoprano,
+    Alto,
+    Tenor,
+    Baritone,
+    Bass,
+    Child,
+}
+
+impl VoiceType {
+    pub const ALL: [VoiceType; 7] = [
+        VoiceType::Soprano,
+        VoiceType::MezzoSoprano,
+        VoiceType::Alto,
+        VoiceType::Tenor,
+        VoiceType::Baritone,
+        VoiceType::Bass,
+        VoiceType::Child,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VoiceType::Soprano => "soprano",
+            VoiceType::MezzoSoprano => "mezzo",
+            VoiceType::Alto => "alto",
+            VoiceType::Tenor => "tenor",
+            VoiceType::Baritone => "baritone",
+            VoiceType::Bass => "bass",
+            VoiceType::Child => "child",
+        }
+    }
+}
+
+impl fmt::Display for VoiceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for VoiceType {
+    type Err = PresetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let vt = match s.as_str() {
+            "mezzo-soprano" | "mezzosoprano" => VoiceType::MezzoSoprano,
+            _ => *VoiceType::ALL
+                .iter()
+                .find(|v| v.name() == s)
+                .ok_or(PresetError::UnknownVoiceType(s.clone()))?,
+        };
+        Ok(vt)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoicePreset {
+    // vocal tract length in cm, and nose length
+    // relative to the tract
+    pub tract_length: f32,
+    pub nose_ratio: f32,
+    pub oversample: u16,
+
+    // glottal parameters, see Glot
+    pub shape: f32,
+    pub aspiration: f32,
+    pub noise_floor: f32,
+    pub jitter: f32,
+    pub shimmer: f32,
+    pub seed: u32,
+
+    pub vibrato_rate: f32,
+    pub vibrato_depth: f32,
+
+    // starting pitch, as a note number
+    pub pitch: f32,
+
+    // default vowel, as DRM regions
+    pub vowel: [f32; 8],
+}
+
+impl Default for VoicePreset {
+    // a neutral adult voice, matches Voice::new
+    fn default() -> Self {
+        VoicePreset {
+            tract_length: 17.0,
+            nose_ratio: 0.63,
+            oversample: 2,
+            shape: 0.476,
+            aspiration: 0.1,
+            noise_floor: 0.287,
+            jitter: 0.0,
+            shimmer: 0.0,
+            seed: 0,
+            vibrato_rate: 6.0,
+            vibrato_depth: 0.03,
+            pitch: 60.0,
+            vowel: [1.011, 0.201, 0.487, 0.440, 1.297, 2.368, 1.059, 2.225],
+        }
+    }
+}
+
+impl VoicePreset {
+    // Presets for the usual choir voice types. These were
+    // tuned by ear on an "ah" vowel.
+    pub fn new(voice_type: VoiceType) -> Self {
+        let base = VoicePreset::default();
+
+        match voice_type {
+            VoiceType::Soprano => VoicePreset {
+                tract_length: 12.3,
+                shape: 0.4,
+                aspiration: 0.1,
+                seed: 111111,
+                vibrato_rate: 6.5,
+                vibrato_depth: 0.05,
+                pitch: 72.0,
+                vowel: [1.773, 0.225, 0.392, 0.5, 1.868, 1.987, 0.392, 3.249],
+                ..base
+            },
+            VoiceType::MezzoSoprano => VoicePreset {
+                tract_length: 13.2,
+                shape: 0.35,
+                aspiration: 0.08,
+                seed: 220202,
+                vibrato_rate: 6.2,
+                vibrato_depth: 0.08,
+                pitch: 67.0,
+                vowel: [1.27, 0.363, 0.446, 0.5, 1.661, 2.678, 1.737, 2.995],
+                ..base
+            },
+            VoiceType::Alto => VoicePreset {
+                tract_length: 14.0,
+                shape: 0.3,
+                aspiration: 0.05,
+                seed: 330303,
+                vibrato_rate: 6.0,
+                vibrato_depth: 0.1,
+                pitch: 64.0,
+                vowel: [0.768, 0.5, 0.5, 0.5, 1.454, 3.368, 3.082, 2.74],
+                ..base
+            },
+            VoiceType::Tenor => VoicePreset {
+                tract_length: 16.0,
+                shape: 0.3,
+                aspiration: 0.04,
+                seed: 12345,
+                vibrato_rate: 6.1,
+                vibrato_depth: 0.2,
+                pitch: 57.0,
+                vowel: [0.225, 0.059, 0.059, 0.082, 0.701, 3.701, 1.725, 1.082],
+                ..base
+            },
+            VoiceType::Baritone => VoicePreset {
+                tract_length: 17.0,
+                shape: 0.25,
+                aspiration: 0.04,
+                seed: 43210,
+                vibrato_rate: 6.1,
+                vibrato_depth: 0.15,
+                pitch: 52.0,
+                vowel: [0.225, 0.345, 0.452, 0.291, 0.601, 3.701, 1.273, 1.594],
+                ..base
+            },
+            VoiceType::Bass => VoicePreset {
+                tract_length: 18.3,
+                shape: 0.2,
+                aspiration: 0.04,
+                seed: 54321,
+                vibrato_rate: 6.1,
+                vibrato_depth: 0.1,
+                pitch: 48.0,
+                vowel: [0.225, 0.63, 0.844, 0.5, 0.5, 3.701, 0.82, 2.106],
+                ..base
+            },
+            VoiceType::Child => VoicePreset {
+                tract_length: 10.5,
+                shape: 0.5,
+                aspiration: 0.15,
+                noise_floor: 0.3,
+                seed: 777,
+                // children rarely use much vibrato
+                vibrato_rate: 5.5,
+                vibrato_depth: 0.02,
+                pitch: 69.0,
+                vowel: [1.773, 0.225, 0.392, 0.5, 1.868, 1.987, 0.392, 3.249],
+                ..base
+            },
+        }
+    }
+}
+
+impl fmt::Display for VoicePreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "tract_length = {}", self.tract_length)?;
+        writeln!(f, "nose_ratio = {}", self.nose_ratio)?;
+        writeln!(f, "oversample = {}", self.oversample)?;
+        writeln!(f, "shape = {}", self.shape)?;
+        writeln!(f, "aspiration = {}", self.aspiration)?;
+        writeln!(f, "noise_floor = {}", self.noise_floor)?;
+        writeln!(f, "jitter = {}", self.jitter)?;
+        writeln!(f, "shimmer = {}", self.shimmer)?;
+        writeln!(f, "seed = {}", self.seed)?;
+        writeln!(f, "vibrato_rate = {}", self.vibrato_rate)?;
+        writeln!(f, "vibrato_depth = {}", self.vibrato_depth)?;
+        writeln!(f, "pitch = {}", self.pitch)?;
+
+        let vowel: Vec<String> = self.vowel.iter().map(|v| v.to_string()).collect();
+        writeln!(f, "vowel = {}", vowel.join(" "))
+    }
+}
+
+fn parse_value<T: FromStr>(val: &str, line: usize) -> Result<T, PresetError> {
+    val.parse::<T>()
+        .map_err(|_| PresetError::Parse(line, format!("invalid value '{}'", val)))
+}
+
+impl FromStr for VoicePreset {
+    type Err = PresetError;
+
+    // Parses "key = value" lines. Blank lines and lines
+    // starting with '#' are ignored. A "voice = <type>" line
+    // starts over from that voice type's preset.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut p = VoicePreset::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            let lnum = i + 1;
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, val) = line.split_once('=').ok_or(
+                PresetError::Parse(lnum, format!("expected 'key = value', got '{}'", line))
+            )?;
+
+            let val = val.trim();
+
+            match key.trim() {
+                "voice" => p = VoicePreset::new(val.parse()?),
+                "tract_length" => p.tract_length = parse_value(val, lnum)?,
+                "nose_ratio" => p.nose_ratio = parse_value(val, lnum)?,
+                "oversample" => p.oversample = parse_value(val, lnum)?,
+                "shape" => p.shape = parse_value(val, lnum)?,
+                "aspiration" => p.aspiration = parse_value(val, lnum)?,
+                "noise_floor" => p.noise_floor = parse_value(val, lnum)?,
+                "jitter" => p.jitter = parse_value(val, lnum)?,
+                "shimmer" => p.shimmer = parse_value(val, lnum)?,
+                "seed" => p.seed = parse_value(val, lnum)?,
+                "vibrato_rate" => p.vibrato_rate = parse_value(val, lnum)?,
+                "vibrato_depth" => p.vibrato_depth = parse_value(val, lnum)?,
+                "pitch" => p.pitch = parse_value(val, lnum)?,
+                "vowel" => {
+                    let regions: Vec<&str> = val.split_whitespace().collect();
+
+                    if regions.len() != 8 {
+                        return Err(PresetError::Parse(
+                            lnum,
+                            format!("vowel needs 8 regions, got {}", regions.len())
+                        ));
+                    }
+
+                    for (r, v) in p.vowel.iter_mut().zip(regions) {
+                        *r = parse_value(v, lnum)?;
+                    }
+                }
+                key => {
+                    return Err(PresetError::Parse(lnum, format!("unknown key '{}'", key)));
+                }
+            }
+        }
+
+        Ok(p)
+    }
+}
+
+// Builds a Voice from a preset, with optional overrides:
+//
+// let voice = VoiceBuilder::new(sr)
+//     .voice_type(VoiceType::Alto)
+//     .vibrato(5.8, 0.15)
+//     .build();
+pub struct VoiceBuilder {
+    sr: usize,
+    preset: VoicePreset,
+}
+
+impl VoiceBuilder {
+    pub fn new(sr: usize) -> Self {
+        VoiceBuilder {
+            sr,
+            preset: VoicePreset::default(),
+        }
+    }
+
+    pub fn from_preset(sr: usize, preset: VoicePreset) -> Self {
+        VoiceBuilder { sr, preset }
+    }
+
+    // replaces all the settings with the preset for this voice type
+    pub fn voice_type(mut self, voice_type: VoiceType) -> Self {
+        self.preset = VoicePreset::new(voice_type);
+        self
+    }
+
+    pub fn tract_length(mut self, length_cm: f32) -> Self {
+        self.preset.tract_length = length_cm;
+        self
+    }
+
+    pub fn nose_ratio(mut self, ratio: f32) -> Self {
+        self.preset.nose_ratio = ratio;
+        self
+    }
+
+    pub fn oversample(mut self, oversample: u16) -> Self {
+        self.preset.oversample = oversample;
+        self
+    }
+
+    pub fn shape(mut self, shape: f32) -> Self {
+        self.preset.shape = shape;
+        self
+    }
+
+    pub fn aspiration(mut self, aspiration: f32) -> Self {
+        self.preset.aspiration = aspiration;
+        self
+    }
+
+    pub fn noise_floor(mut self, noise_floor: f32) -> Self {
+        self.preset.noise_floor = noise_floor;
+        self
+    }
+
+    pub fn jitter(mut self, percent: f32) -> Self {
+        self.preset.jitter = percent;
+        self
+    }
+
+    pub fn shimmer(mut self, percent: f32) -> Self {
+        self.preset.shimmer = percent;
+        self
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.preset.seed = seed;
+        self
+    }
+
+    pub fn vibrato(mut self, rate: f32, depth: f32) -> Self {
+        self.preset.vibrato_rate = rate;
+        self.preset.vibrato_depth = depth;
+        self
+    }
+
+    pub fn pitch(mut self, pitch: f32) -> Self {
+        self.preset.pitch = pitch;
+        self
+    }
+
+    pub fn vowel(mut self, vowel: [f32; 8]) -> Self {
+        self.preset.vowel = vowel;
+        self
+    }
+
+    pub fn preset(&self) -> &VoicePreset {
+        &self.preset
+    }
+
+    pub fn build(&self) -> Voice<Glot> {
+        let p = &self.preset;
+        let mut v = Voice::with_nose_ratio(
+            self.sr,
+            p.tract_length,
+            p.nose_ratio,
+            p.oversample,
+            Glot::new(self.sr)
+        );
+
+        v.glottis.set_shape(p.shape);
+        v.glottis.set_aspiration(p.aspiration);
+        v.glottis.set_noise_floor(p.noise_floor);
+        v.glottis.set_jitter(p.jitter);
+        v.glottis.set_shimmer(p.shimmer);
+        v.glottis.srand(p.seed);
+        // separate stream, so jitter doesn't follow the noise
+        v.glottis.srand_perturbation(p.seed.wrapping_add(1));
+        v.vibrato_rate(p.vibrato_rate);
+        v.vibrato_depth(p.vibrato_depth);
+        v.pitch = p.pitch;
+        v.tract.drm(&p.vowel);
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        for vt in VoiceType::ALL {
+            let preset = VoicePreset::new(vt);
+            let text = preset.to_string();
+            let parsed: VoicePreset = text.parse().unwrap();
+            assert_eq!(preset, parsed);
+        }
+    }
+
+    #[test]
+    fn test_overrides() {
+        let text = "# a darker tenor\nvoice = tenor\n\ntract_length = 16.8\n";
+        let p: VoicePreset = text.parse().unwrap();
+        assert_eq!(p.tract_length, 16.8);
+        assert_eq!(p.vibrato_depth, VoicePreset::new(VoiceType::Tenor).vibrato_depth);
+
+        assert_eq!(
+            "pitch = high".parse::<VoicePreset>(),
+            Err(PresetError::Parse(1, "invalid value 'high'".to_string()))
+        );
+        assert!("vowel = 1 2 3".parse::<VoicePreset>().is_err());
+        assert!("colour = blue".parse::<VoicePreset>().is_err());
+        assert_eq!(
+            "voice = countertenor".parse::<VoicePreset>(),
+            Err(PresetError::UnknownVoiceType("countertenor".to_string()))
+        );
+        assert_eq!("Mezzo-Soprano".parse::<VoiceType>(), Ok(VoiceType::MezzoSoprano));
+    }
+}
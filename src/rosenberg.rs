@@ -0,0 +1,118 @@
+use crate::GlottalSource;
+use std::f32::consts::PI;
+
+// Rosenberg glottal pulse model (Rosenberg 1971, "type C").
+// The flow is a raised cosine during the opening phase,
+// followed by a quarter cosine during the closing phase,
+// then stays closed for the rest of the period. The output
+// is the derivative of that flow.
+pub struct Rosenberg {
+    freq: f32,
+    onedsr: f32,
+    phs: f32,
+
+    // opening phase, as a fraction of the period (Tp)
+    open: f32,
+
+    // closing phase, as a fraction of the period (Tn)
+    close: f32,
+
+    // phase and triggers from the last tick
+    last_phs: f32,
+    period_trig: f32,
+    closure_trig: f32,
+}
+
+impl Rosenberg {
+    pub fn new(sr: usize) -> Self {
+        Rosenberg {
+            freq: 140.0,
+            onedsr: 1.0 / sr as f32,
+            phs: 0.0,
+            // Rosenberg's preferred values: 40% and 16%
+            open: 0.4,
+            close: 0.16,
+            last_phs: 0.0,
+            period_trig: 0.0,
+            closure_trig: 0.0,
+        }
+    }
+
+    pub fn set_open_phase(&mut self, open: f32) {
+        self.open = open.clamp(0.01, 0.98);
+        self.close = self.close.min(0.99 - self.open);
+    }
+
+    pub fn set_closing_phase(&mut self, close: f32) {
+        self.close = close.clamp(0.01, 0.99 - self.open);
+    }
+}
+
+impl GlottalSource for Rosenberg {
+    fn set_freq(&mut self, freq: f32) {
+        self.freq = freq;
+    }
+
+    fn tick(&mut self) -> f32 {
+        let t = self.phs;
+        let tp = self.open;
+        let tn = self.close;
+
+        // the phasor has wrapped (a frequency of 0 holds it still)
+        self.period_trig = if t < self.last_phs { 1.0 } else { 0.0 };
+
+        // complete closure at the end of the closing phase
+        let te = tp + tn;
+        self.closure_trig = if self.last_phs < te && t >= te {
+            1.0
+        } else {
+            0.0
+        };
+        self.last_phs = t;
+
+        let out = if t < tp {
+            0.5 * (PI / tp) * (PI * t / tp).sin()
+        } else if t < tp + tn {
+            -(PI / (2.0 * tn)) * (PI * (t - tp) / (2.0 * tn)).sin()
+        } else {
+            0.0
+        };
+
+        self.phs += self.freq * self.onedsr;
+
+        if self.phs >= 1.0 {
+            self.phs -= 1.0;
+        }
+
+        // negative peak is at the end of the closing phase,
+        // scale it to be -1
+        out * (2.0 * tn / PI)
+    }
+
+    fn period_trigger(&self) -> f32 {
+        self.period_trig
+    }
+
+    fn closure_trigger(&self) -> f32 {
+        self.closure_trig
+    }
+
+    fn phase(&self) -> f32 {
+        self.last_phs
+    }
+
+    fn opening(&self) -> f32 {
+        let t = self.last_phs;
+        let tp = self.open;
+        let tn = self.close;
+
+        // the flow itself, which peaks at 1
+        if t < tp {
+            0.5 * (1.0 - (PI * t / tp).cos())
+        } else if t < tp + tn {
+            (PI * (t - tp) / (2.0 * tn)).cos()
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,1017 @@
+// Standard MIDI File (format 0 and 1) reading and rendering.
+//
+// Smf holds the parsed file: the tracks, each a list of events
+// at absolute times (in ticks). Only the events voxbox uses are
+// kept: notes, controllers, pitch bend, tempo, lyrics and track
+// names.
+//
+// SmfRenderer plays a file on a set of voices, one per part
+// (a part is a track, or a channel of a format 0 file). Parts
+// are monophonic. Notes become a pitch gesture, velocity sets
+// the vocal effort, and:
+//
+// pitch bend: pitch, +/- 2 semitones
+// CC1 (modulation): vibrato depth
+// CC7 (volume), CC11 (expression): level
+// CC16, CC17: tongue position and diameter (the vowel)
+//
+// Lyric events are read as phonemes (see PhonemeTimeline) and
+// sung on the next note, with the last vowel stretched to fill
+// it: "HH AH L OW" or "h ə l oʊ". Other lyrics are ignored.
+
+use std::fmt;
+use crate::Behavior;
+use crate::Envelope;
+use crate::GestureVertex;
+use crate::Glot;
+use crate::LinearGestureBuilder;
+use crate::MonoWav;
+use crate::PhonemeSequencer;
+use crate::PhonemeTimeline;
+use crate::SignalGenerator;
+use crate::Voice;
+use crate::VoiceBuilder;
+use crate::VoicePreset;
+
+const DEFAULT_TEMPO: u32 = 500000;
+const BEND_RANGE: f32 = 2.0;
+const MAX_VIBRATO: f32 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmfError {
+    Io(String),
+    // not a MIDI file (no MThd header)
+    NotSmf,
+    UnsupportedFormat(u16),
+    // byte offset where the file ended early
+    UnexpectedEnd(usize),
+    // byte offset and description
+    Invalid(usize, String),
+}
+
+impl fmt::Display for SmfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SmfError::Io(msg) => write!(f, "{}", msg),
+            SmfError::NotSmf => write!(f, "not a standard MIDI file"),
+            SmfError::UnsupportedFormat(fmt) => {
+                write!(f, "unsupported MIDI file format {}", fmt)
+            }
+            SmfError::UnexpectedEnd(pos) => write!(f, "unexpected end of file at byte {}", pos),
+            SmfError::Invalid(pos, msg) => write!(f, "byte {}: {}", pos, msg),
+        }
+    }
+}
+
+impl std::error::Error for SmfError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmfEventKind {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    Controller { channel: u8, controller: u8, value: u8 },
+    // -8192 to 8191
+    PitchBend { channel: u8, value: i16 },
+    // microseconds per quarter note
+    Tempo(u32),
+    Lyric(String),
+    TrackName(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmfEvent {
+    // absolute time, in ticks
+    pub tick: u64,
+    pub kind: SmfEventKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SmfTrack {
+    pub events: Vec<SmfEvent>,
+}
+
+impl SmfTrack {
+    pub fn name(&self) -> Option<&str> {
+        self.events.iter().find_map(|e| match &e.kind {
+            SmfEventKind::TrackName(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Division {
+    TicksPerQuarter(u16),
+    // SMPTE frames per second and ticks per frame
+    Smpte(u8, u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Smf {
+    pub format: u16,
+    pub division: Division,
+    pub tracks: Vec<SmfTrack>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, SmfError> {
+        let b = *self.data.get(self.pos).ok_or(SmfError::UnexpectedEnd(self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], SmfError> {
+        if self.pos + n > self.data.len() {
+            return Err(SmfError::UnexpectedEnd(self.data.len()));
+        }
+
+        let b = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(b)
+    }
+
+    fn u16(&mut self) -> Result<u16, SmfError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, SmfError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // variable length quantity, at most 4 bytes
+    fn vlq(&mut self) -> Result<u32, SmfError> {
+        let start = self.pos;
+        let mut val = 0u32;
+
+        for _ in 0..4 {
+            let b = self.u8()?;
+            val = (val << 7) | (b & 0x7f) as u32;
+
+            if b & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+
+        Err(SmfError::Invalid(start, "variable length quantity too long".to_string()))
+    }
+}
+
+impl Smf {
+    pub fn open(filename: &str) -> Result<Smf, SmfError> {
+        let data = std::fs::read(filename)
+            .map_err(|e| SmfError::Io(format!("{}: {}", filename, e)))?;
+        Smf::parse(&data)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Smf, SmfError> {
+        let mut r = Reader { data, pos: 0 };
+
+        if data.len() < 4 || r.bytes(4)? != b"MThd" {
+            return Err(SmfError::NotSmf);
+        }
+
+        // the header is at least 6 bytes, and has to fit
+        let len_pos = r.pos;
+        let len = r.u32()? as usize;
+        let header_end = r.pos.saturating_add(len);
+
+        if len < 6 {
+            let msg = format!("header length {} is too short", len);
+            return Err(SmfError::Invalid(len_pos, msg));
+        }
+
+        if header_end > data.len() {
+            return Err(SmfError::UnexpectedEnd(data.len()));
+        }
+
+        let format = r.u16()?;
+        let ntracks = r.u16()?;
+        let division = r.u16()?;
+        r.pos = header_end;
+
+        if format > 1 {
+            return Err(SmfError::UnsupportedFormat(format));
+        }
+
+        let division = if division & 0x8000 != 0 {
+            let fps = -((division >> 8) as u8 as i8) as u8;
+            Division::Smpte(fps, division as u8)
+        } else {
+            Division::TicksPerQuarter(division)
+        };
+
+        let mut tracks = vec![];
+
+        while tracks.len() < ntracks as usize && r.pos < data.len() {
+            let id = r.bytes(4)?;
+            let len = r.u32()? as usize;
+            let start = r.pos;
+            let chunk = r.bytes(len)?;
+
+            // unknown chunks are skipped, as the spec asks
+            if id == b"MTrk" {
+                tracks.push(parse_track(chunk, start)?);
+            }
+        }
+
+        Ok(Smf {
+            format,
+            division,
+            tracks,
+        })
+    }
+
+    // ticks per time unit, and the default units per second:
+    // quarter notes at 120bpm, or seconds for SMPTE time
+    fn unit(&self) -> (u32, f64) {
+        match self.division {
+            Division::TicksPerQuarter(tpq) => (tpq.max(1) as u32, 1e6 / DEFAULT_TEMPO as f64),
+            Division::Smpte(fps, tpf) => ((fps as u32 * tpf as u32).max(1), 1.0),
+        }
+    }
+
+    // tempo changes from all tracks, as (tick, microseconds
+    // per quarter). SMPTE files ignore tempo.
+    pub fn tempo_map(&self) -> Vec<(u64, u32)> {
+        if let Division::Smpte(..) = self.division {
+            return vec![];
+        }
+
+        let mut map: Vec<(u64, u32)> = self
+            .tracks
+            .iter()
+            .flat_map(|t| t.events.iter())
+            .filter_map(|e| match e.kind {
+                SmfEventKind::Tempo(t) => Some((e.tick, t)),
+                _ => None,
+            })
+            .collect();
+
+        map.sort_by_key(|t| t.0);
+        map
+    }
+
+    // converts a time in ticks to seconds
+    pub fn seconds(&self, tick: u64) -> f64 {
+        seconds(&self.tempo_map(), self.unit(), tick)
+    }
+
+    // time of the last event, in seconds
+    pub fn duration(&self) -> f64 {
+        let last = self
+            .tracks
+            .iter()
+            .filter_map(|t| t.events.last())
+            .map(|e| e.tick)
+            .max()
+            .unwrap_or(0);
+
+        self.seconds(last)
+    }
+}
+
+fn seconds(tempo_map: &[(u64, u32)], unit: (u32, f64), tick: u64) -> f64 {
+    let (ticks_per_unit, default_rate) = unit;
+    let tpu = ticks_per_unit as f64;
+    let mut time = 0.0;
+    let mut last = 0;
+    let mut rate = default_rate;
+
+    for &(t, tempo) in tempo_map.iter().take_while(|t| t.0 < tick) {
+        time += (t - last) as f64 / tpu / rate;
+        last = t;
+        rate = 1e6 / tempo.max(1) as f64;
+    }
+
+    time + (tick - last) as f64 / tpu / rate
+}
+
+fn parse_track(data: &[u8], offset: usize) -> Result<SmfTrack, SmfError> {
+    let mut r = Reader { data, pos: 0 };
+    let mut events = vec![];
+    let mut tick = 0u64;
+    let mut running = None;
+
+    // positions in errors are relative to the whole file
+    let err = |e: SmfError| match e {
+        SmfError::UnexpectedEnd(p) => SmfError::UnexpectedEnd(p + offset),
+        SmfError::Invalid(p, msg) => SmfError::Invalid(p + offset, msg),
+        e => e,
+    };
+
+    while r.pos < data.len() {
+        tick += r.vlq().map_err(err)? as u64;
+        let start = r.pos;
+        let mut status = r.u8().map_err(err)?;
+
+        // running status: the data byte belongs to the
+        // previous channel message
+        if status < 0x80 {
+            status = running.ok_or_else(|| {
+                SmfError::Invalid(start + offset, "data byte without status".to_string())
+            })?;
+            r.pos -= 1;
+        }
+
+        match status {
+            0xff => {
+                running = None;
+                let kind = r.u8().map_err(err)?;
+                let len = r.vlq().map_err(err)? as usize;
+                let body = r.bytes(len).map_err(err)?;
+                let text = || String::from_utf8_lossy(body).trim().to_string();
+
+                let ev = match kind {
+                    0x03 => Some(SmfEventKind::TrackName(text())),
+                    0x05 => Some(SmfEventKind::Lyric(text())),
+                    0x51 if len == 3 => Some(SmfEventKind::Tempo(
+                        (body[0] as u32) << 16 | (body[1] as u32) << 8 | body[2] as u32,
+                    )),
+                    // end of track
+                    0x2f => break,
+                    _ => None,
+                };
+
+                if let Some(kind) = ev {
+                    events.push(SmfEvent { tick, kind });
+                }
+            }
+            0xf0 | 0xf7 => {
+                running = None;
+                let len = r.vlq().map_err(err)? as usize;
+                r.bytes(len).map_err(err)?;
+            }
+            0x80..=0xef => {
+                running = Some(status);
+                let channel = status & 0x0f;
+                let a = r.u8().map_err(err)? & 0x7f;
+
+                // program change and channel pressure
+                // have a single data byte
+                let b = match status & 0xf0 {
+                    0xc0 | 0xd0 => 0,
+                    _ => r.u8().map_err(err)? & 0x7f,
+                };
+
+                let ev = match status & 0xf0 {
+                    0x80 => Some(SmfEventKind::NoteOff { channel, note: a }),
+                    0x90 if b == 0 => Some(SmfEventKind::NoteOff { channel, note: a }),
+                    0x90 => Some(SmfEventKind::NoteOn {
+                        channel,
+                        note: a,
+                        velocity: b,
+                    }),
+                    0xb0 => Some(SmfEventKind::Controller {
+                        channel,
+                        controller: a,
+                        value: b,
+                    }),
+                    0xe0 => Some(SmfEventKind::PitchBend {
+                        channel,
+                        value: ((b as i16) << 7 | a as i16) - 8192,
+                    }),
+                    _ => None,
+                };
+
+                if let Some(kind) = ev {
+                    events.push(SmfEvent { tick, kind });
+                }
+            }
+            _ => {
+                return Err(SmfError::Invalid(
+                    start + offset,
+                    format!("unexpected status byte 0x{:02x}", status),
+                ));
+            }
+        }
+    }
+
+    Ok(SmfTrack { events })
+}
+
+enum PartEvent {
+    // length is the time (in seconds) until the next note or
+    // the release, whichever comes first
+    NoteOn { velocity: f32, length: f32 },
+    NoteOff,
+    Controller { controller: u8, value: f32 },
+    PitchBend(f32),
+    Lyric(String),
+}
+
+struct Part {
+    name: String,
+    voice: Voice<Glot>,
+    env: Envelope,
+    pitch: LinearGestureBuilder,
+    events: Vec<(usize, PartEvent)>,
+    pos: usize,
+
+    // number of notes held
+    held: usize,
+    gate: f32,
+    bend: f32,
+    level: f32,
+    tongue: Option<(f32, f32)>,
+
+    lyric: Option<PhonemeTimeline>,
+    phonemes: Option<PhonemeSequencer>,
+}
+
+impl Part {
+    fn tick(&mut self, clk: f32, now: usize, sr: usize) -> f32 {
+        while let Some((t, ev)) = self.events.get(self.pos) {
+            if *t > now {
+                break;
+            }
+
+            match ev {
+                PartEvent::NoteOn { velocity, length } => {
+                    if self.held == 0 {
+                        self.voice.onset();
+                    }
+                    self.held += 1;
+                    self.gate = 1.0;
+
+                    // soft to loud, but not all the way
+                    // to the phonation threshold
+                    self.voice.glottis.set_effort(0.15 + 0.7 * velocity);
+
+                    if let Some(mut tl) = self.lyric.take() {
+                        tl.stretch_to(*length);
+                        self.phonemes = Some(PhonemeSequencer::new(sr, tl));
+                    }
+                }
+                PartEvent::NoteOff => {
+                    self.held = self.held.saturating_sub(1);
+                    if self.held == 0 {
+                        self.gate = 0.0;
+                    }
+                }
+                PartEvent::Controller { controller, value } => match controller {
+                    1 => self.voice.vibrato_depth(value * MAX_VIBRATO),
+                    7 | 11 => self.level = *value,
+                    16 => {
+                        let (_, diam) = self.tongue.unwrap_or((0.5, 0.5));
+                        self.tongue = Some((*value, diam));
+                    }
+                    17 => {
+                        let (pos, _) = self.tongue.unwrap_or((0.5, 0.5));
+                        self.tongue = Some((pos, *value));
+                    }
+                    _ => {}
+                },
+                PartEvent::PitchBend(bend) => self.bend = bend * BEND_RANGE,
+                PartEvent::Lyric(text) => self.lyric = PhonemeTimeline::parse(text).ok(),
+            }
+
+            // tongue changes are applied once all the
+            // events at this time are read
+            if let PartEvent::Controller { controller: 16 | 17, .. } = ev {
+                let (pos, diam) = self.tongue.unwrap();
+                self.voice.tract.tongue_shape(pos, diam);
+            }
+
+            self.pos += 1;
+        }
+
+        self.voice.pitch = self.pitch.tick(clk) + self.bend;
+
+        let env = self.env.tick(self.gate);
+
+        let out = match &mut self.phonemes {
+            Some(seq) => {
+                let state = seq.tick_state();
+                state.apply(&mut self.voice);
+                self.voice.tick()
+            }
+            None => self.voice.tick(),
+        };
+
+        out * env * self.level
+    }
+}
+
+// Renders a Smf on voices, one per part. The voices start from
+// the default preset, see set_preset.
+pub struct SmfRenderer {
+    sr: usize,
+    parts: Vec<Part>,
+    stems: Vec<f32>,
+    // clock for the gestures, in time units (quarter notes,
+    // or seconds). Kept in double precision: a Phasor drifts
+    // audibly from the note times over a few minutes.
+    clock: f64,
+    clock_incr: f64,
+    // sample times of tempo changes, and the clock
+    // frequency (time units per second) from then on
+    tempo: Vec<(usize, f64)>,
+    tempo_pos: usize,
+    time: usize,
+    length: usize,
+}
+
+impl SmfRenderer {
+    pub fn new(sr: usize, smf: &Smf) -> Self {
+        let unit = smf.unit();
+        let tempo_map = smf.tempo_map();
+        let sample = |tick: u64| (seconds(&tempo_map, unit, tick) * sr as f64).round() as usize;
+
+        let mut tempo = vec![(0, unit.1)];
+        for &(tick, t) in tempo_map.iter() {
+            tempo.push((sample(tick), 1e6 / t.max(1) as f64));
+        }
+
+        // parts: a track, or each channel of a format 0 file
+        let mut parts = vec![];
+
+        for (i, track) in smf.tracks.iter().enumerate() {
+            let mut channels: Vec<u8> = track
+                .events
+                .iter()
+                .filter_map(|e| match e.kind {
+                    SmfEventKind::NoteOn { channel, .. } => Some(channel),
+                    _ => None,
+                })
+                .collect();
+
+            channels.sort();
+            channels.dedup();
+
+            if smf.format == 1 && !channels.is_empty() {
+                channels = vec![channels[0]];
+            }
+
+            for (c, &channel) in channels.iter().enumerate() {
+                let name = match (track.name(), smf.format) {
+                    (Some(name), 1) => name.to_string(),
+                    _ => format!("track {} channel {}", i, channel + 1),
+                };
+
+                // format 1 tracks take all channels, and
+                // lyrics go to the first part of a track
+                let only = if smf.format == 0 { Some(channel) } else { None };
+                let events = part_events(track, only, c == 0);
+                parts.push(Part::new(sr, name, &events, &sample, unit.0));
+            }
+        }
+
+        let length = sample(
+            smf.tracks
+                .iter()
+                .filter_map(|t| t.events.last())
+                .map(|e| e.tick)
+                .max()
+                .unwrap_or(0),
+        );
+
+        SmfRenderer {
+            sr,
+            stems: vec![0.0; parts.len()],
+            parts,
+            clock: 0.0,
+            clock_incr: 0.0,
+            tempo,
+            tempo_pos: 0,
+            time: 0,
+            length,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    // track name, or track and channel numbers
+    pub fn part_name(&self, index: usize) -> &str {
+        &self.parts[index].name
+    }
+
+    pub fn voice(&mut self, index: usize) -> &mut Voice<Glot> {
+        &mut self.parts[index].voice
+    }
+
+    // replaces the voice of a part with one built from a preset
+    pub fn set_preset(&mut self, index: usize, preset: &VoicePreset) {
+        let part = &mut self.parts[index];
+        part.voice = VoiceBuilder::from_preset(self.sr, preset.clone()).build();
+    }
+
+    pub fn set_attack(&mut self, time: f32) {
+        for p in self.parts.iter_mut() {
+            p.env.set_attack(time);
+        }
+    }
+
+    pub fn set_release(&mut self, time: f32) {
+        for p in self.parts.iter_mut() {
+            p.env.set_release(time);
+        }
+    }
+
+    // length of the file, in seconds
+    pub fn duration(&self) -> f32 {
+        self.length as f32 / self.sr as f32
+    }
+
+    pub fn done(&self) -> bool {
+        self.time >= self.length
+    }
+
+    // Computes the next sample of each part, returned
+    // as separate stems.
+    pub fn tick_stems(&mut self) -> &[f32] {
+        while let Some(&(t, freq)) = self.tempo.get(self.tempo_pos) {
+            if t > self.time {
+                break;
+            }
+            self.clock_incr = freq / self.sr as f64;
+            self.tempo_pos += 1;
+        }
+
+        let clk = self.clock.fract() as f32;
+        self.clock += self.clock_incr;
+
+        for (part, out) in self.parts.iter_mut().zip(self.stems.iter_mut()) {
+            *out = part.tick(clk, self.time, self.sr);
+        }
+
+        self.time += 1;
+        &self.stems
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        self.tick_stems().iter().sum()
+    }
+
+    // Renders the whole file (plus a tail, in seconds, for the
+    // last release) to a WAV file, with the given gain.
+    pub fn render(&mut self, filename: &str, tail: f32, gain: f32) {
+        let mut wav = MonoWav::new(filename);
+        let end = self.length + (tail.max(0.0) * self.sr as f32) as usize;
+
+        while self.time < end {
+            let out = self.tick() * gain;
+            wav.tick(out);
+        }
+    }
+}
+
+// Collects the events of one part. Overlapping notes are
+// played legato; of notes starting together, the highest wins.
+fn part_events(
+    track: &SmfTrack,
+    channel: Option<u8>,
+    lyrics: bool
+) -> Vec<(u64, SmfEventKind)> {
+    let in_part = |c: &u8| channel.map(|ch| ch == *c).unwrap_or(true);
+
+    let mut events: Vec<(u64, SmfEventKind)> = vec![];
+
+    for e in track.events.iter() {
+        let keep = match &e.kind {
+            SmfEventKind::NoteOn { channel, .. } => in_part(channel),
+            SmfEventKind::NoteOff { channel, .. } => in_part(channel),
+            SmfEventKind::Controller { channel, .. } => in_part(channel),
+            SmfEventKind::PitchBend { channel, .. } => in_part(channel),
+            SmfEventKind::Lyric(_) => lyrics,
+            _ => false,
+        };
+
+        if !keep {
+            continue;
+        }
+
+        // chords: keep the top note
+        if let SmfEventKind::NoteOn { note, .. } = e.kind {
+            let prev = events
+                .iter_mut()
+                .rev()
+                .take_while(|p| p.0 == e.tick)
+                .find(|p| matches!(p.1, SmfEventKind::NoteOn { .. }));
+
+            if let Some(prev) = prev {
+                if let SmfEventKind::NoteOn { note: n, .. } = prev.1 {
+                    if note > n {
+                        *prev = (e.tick, e.kind.clone());
+                    }
+                }
+                continue;
+            }
+        }
+
+        events.push((e.tick, e.kind.clone()));
+    }
+
+    // note offs of dropped chord notes have nothing to release
+    let mut held: Vec<u8> = vec![];
+    events.retain(|(_, kind)| match kind {
+        SmfEventKind::NoteOn { note, .. } => {
+            held.push(*note);
+            true
+        }
+        SmfEventKind::NoteOff { note, .. } => {
+            match held.iter().position(|n| n == note) {
+                Some(i) => {
+                    held.remove(i);
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => true,
+    });
+
+    events
+}
+
+impl Part {
+    fn new(
+        sr: usize,
+        name: String,
+        events: &[(u64, SmfEventKind)],
+        sample: &dyn Fn(u64) -> usize,
+        ticks_per_unit: u32
+    ) -> Self {
+        let mut voice = VoiceBuilder::new(sr).build();
+        voice.vibrato.set_onset(0.2, 0.3);
+
+        let mut env = Envelope::new(sr);
+        env.set_attack(0.03);
+        env.set_release(0.15);
+
+        let seconds = |a: u64, b: u64| (sample(b) - sample(a)) as f32 / sr as f32;
+
+        // note starts, with the end of the note (the next
+        // note or the release), and the note number
+        let mut notes: Vec<(u64, u64, u8)> = vec![];
+        let mut held = 0;
+
+        for (tick, kind) in events.iter() {
+            match kind {
+                SmfEventKind::NoteOn { note, .. } => {
+                    if let Some(last) = notes.last_mut() {
+                        if held > 0 {
+                            last.1 = *tick;
+                        }
+                    }
+                    notes.push((*tick, *tick, *note));
+                    held += 1;
+                }
+                SmfEventKind::NoteOff { .. } => {
+                    held -= 1;
+                    if held == 0 {
+                        if let Some(last) = notes.last_mut() {
+                            last.1 = *tick;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut part_events = vec![];
+        let mut n = 0;
+
+        for (tick, kind) in events.iter() {
+            let ev = match kind {
+                SmfEventKind::NoteOn { velocity, .. } => {
+                    let (start, end, _) = notes[n];
+                    n += 1;
+                    PartEvent::NoteOn {
+                        velocity: *velocity as f32 / 127.0,
+                        length: seconds(start, end),
+                    }
+                }
+                SmfEventKind::NoteOff { .. } => PartEvent::NoteOff,
+                SmfEventKind::Controller { controller, value, .. } => PartEvent::Controller {
+                    controller: *controller,
+                    value: *value as f32 / 127.0,
+                },
+                SmfEventKind::PitchBend { value, .. } => PartEvent::PitchBend(*value as f32 / 8192.0),
+                SmfEventKind::Lyric(text) => PartEvent::Lyric(text.clone()),
+                _ => continue,
+            };
+
+            part_events.push((sample(*tick), ev));
+        }
+
+        let mut pitch = LinearGestureBuilder::new();
+        let vertex = |val: u8, ticks: u64, bhvr| GestureVertex {
+            val: val as f32,
+            num: ticks_per_unit,
+            den: ticks.max(1) as u32,
+            bhvr,
+        };
+
+        // the pitch path: each note glides into the next one
+        // if they are joined, otherwise it holds through the rest
+        let mut time = 0;
+
+        for (i, &(start, end, note)) in notes.iter().enumerate() {
+            if start > time {
+                let prev = if i > 0 { notes[i - 1].2 } else { note };
+                pitch.append(vertex(prev, start - time, Behavior::Step));
+            }
+
+            let legato = notes.get(i + 1).map(|n| n.0 == end).unwrap_or(false);
+            let bhvr = if legato { Behavior::GlissTiny } else { Behavior::Step };
+
+            if end > start {
+                pitch.append(vertex(note, end - start, bhvr));
+            }
+
+            time = end.max(start);
+        }
+
+        let last = notes.last().map(|n| n.2).unwrap_or(60);
+        pitch.append(vertex(last, ticks_per_unit as u64, Behavior::Step));
+        pitch.done();
+
+        Part {
+            name,
+            voice,
+            env,
+            pitch,
+            events: part_events,
+            pos: 0,
+            held: 0,
+            gate: 0.0,
+            bend: 0.0,
+            level: 1.0,
+            tongue: None,
+            lyric: None,
+            phonemes: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // builds a file from tracks of (delta, event bytes)
+    fn smf(format: u16, tpq: u16, tracks: &[Vec<(u32, Vec<u8>)>]) -> Vec<u8> {
+        let mut out = b"MThd".to_vec();
+        out.extend(6u32.to_be_bytes());
+        out.extend(format.to_be_bytes());
+        out.extend((tracks.len() as u16).to_be_bytes());
+        out.extend(tpq.to_be_bytes());
+
+        for track in tracks {
+            let mut data = vec![];
+            for (delta, bytes) in track {
+                let mut vlq = vec![(*delta & 0x7f) as u8];
+                let mut d = *delta >> 7;
+                while d > 0 {
+                    vlq.insert(0, (d & 0x7f) as u8 | 0x80);
+                    d >>= 7;
+                }
+                data.extend(vlq);
+                data.extend(bytes);
+            }
+            data.extend([0x00, 0xff, 0x2f, 0x00]);
+
+            out.extend(b"MTrk");
+            out.extend((data.len() as u32).to_be_bytes());
+            out.extend(data);
+        }
+
+        out
+    }
+
+    #[test]
+    fn test_parse() {
+        let tempo = vec![(0, vec![0xff, 0x51, 0x03, 0x07, 0xa1, 0x20])];
+        let notes = vec![
+            (0, vec![0xff, 0x03, 0x03, b'S', b'o', b'p']),
+            (0, vec![0xff, 0x05, 0x02, b'A', b'H']),
+            (0, vec![0x90, 60, 100]),
+            // running status, note off as velocity 0
+            (480, vec![60, 0]),
+            (0, vec![62, 90]),
+            (200, vec![0xe0, 0x00, 0x60]),
+            (280, vec![0x80, 62, 0]),
+        ];
+
+        let data = smf(1, 480, &[tempo, notes]);
+        let f = Smf::parse(&data).unwrap();
+
+        assert_eq!(f.format, 1);
+        assert_eq!(f.division, Division::TicksPerQuarter(480));
+        assert_eq!(f.tracks.len(), 2);
+        assert_eq!(f.tracks[0].events[0].kind, SmfEventKind::Tempo(500000));
+
+        let t = &f.tracks[1];
+        assert_eq!(t.name(), Some("Sop"));
+        assert_eq!(t.events[1].kind, SmfEventKind::Lyric("AH".to_string()));
+        assert_eq!(t.events[3], SmfEvent {
+            tick: 480,
+            kind: SmfEventKind::NoteOff { channel: 0, note: 60 },
+        });
+        assert_eq!(t.events[4].kind, SmfEventKind::NoteOn {
+            channel: 0,
+            note: 62,
+            velocity: 90,
+        });
+        assert_eq!(t.events[5].kind, SmfEventKind::PitchBend { channel: 0, value: 4096 });
+        assert_eq!(t.events[6].tick, 960);
+
+        // 120bpm: a quarter note is half a second
+        assert!((f.seconds(960) - 1.0).abs() < 1e-9);
+        assert!((f.duration() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Smf::parse(b"RIFF0000"), Err(SmfError::NotSmf));
+
+        let data = smf(2, 96, &[]);
+        assert_eq!(Smf::parse(&data), Err(SmfError::UnsupportedFormat(2)));
+
+        let mut data = smf(0, 96, &[vec![(0, vec![0x90, 60, 100])]]);
+        data.truncate(data.len() - 6);
+        assert!(matches!(Smf::parse(&data), Err(SmfError::UnexpectedEnd(_))));
+
+        // a data byte with no status to run from
+        let data = smf(0, 96, &[vec![(0, vec![60, 100])]]);
+        assert!(matches!(Smf::parse(&data), Err(SmfError::Invalid(23, _))));
+
+        // header lengths that would back up into the header,
+        // or run past the end of the file
+        let mut data = smf(0, 96, &[vec![(0, vec![0x90, 60, 100])]]);
+        data[4..8].copy_from_slice(&2u32.to_be_bytes());
+        assert!(matches!(Smf::parse(&data), Err(SmfError::Invalid(4, _))));
+        data[4..8].copy_from_slice(&1000u32.to_be_bytes());
+        assert_eq!(Smf::parse(&data), Err(SmfError::UnexpectedEnd(data.len())));
+        data[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(Smf::parse(&data), Err(SmfError::UnexpectedEnd(data.len())));
+    }
+
+    #[test]
+    fn test_tempo_change() {
+        let tempo = vec![
+            (0, vec![0xff, 0x51, 0x03, 0x07, 0xa1, 0x20]),
+            // 60bpm after one quarter
+            (96, vec![0xff, 0x51, 0x03, 0x0f, 0x42, 0x40]),
+        ];
+        let f = Smf::parse(&smf(1, 96, &[tempo])).unwrap();
+        assert!((f.seconds(96) - 0.5).abs() < 1e-9);
+        assert!((f.seconds(192) - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_render_pitch() {
+        let sr = 44100;
+        let mut notes = vec![];
+        let melody = [60u8, 64, 67, 65, 62, 72, 60, 55];
+
+        // quarter notes at 120bpm, the last pair with a rest between
+        for (i, &n) in melody.iter().enumerate() {
+            let rest = if i == 7 { 240 } else { 0 };
+            notes.push((rest, vec![0x90, n, 100]));
+            notes.push((480 - rest, vec![0x80, n, 0]));
+        }
+
+        let f = Smf::parse(&smf(0, 480, &[notes])).unwrap();
+        let mut r = SmfRenderer::new(sr, &f);
+        assert_eq!(r.len(), 1);
+
+        // pitch (without vibrato) in the middle of each note
+        r.voice(0).vibrato_depth(0.0);
+        let mut pitches = vec![];
+        let mut silent = true;
+
+        for n in 0..(sr * 4) {
+            r.tick();
+            let t = n as f32 / sr as f32;
+            if n % (sr / 2) == sr / 4 && t < 3.5 {
+                pitches.push(r.voice(0).pitch);
+            }
+
+            // gate is off during the rest
+            if (3.55..3.7).contains(&t) {
+                silent &= r.parts[0].gate == 0.0;
+            }
+        }
+
+        assert!(silent);
+        let expected: Vec<f32> = melody.iter().map(|&n| n as f32).collect();
+
+        assert_eq!(pitches.len(), 7);
+        for (p, e) in pitches.iter().zip(expected.iter()) {
+            assert!((p - e).abs() < 0.01, "{:?}", pitches);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::tract::GLOTTAL_REFLECTION;
+use crate::tract::SPEED_OF_SOUND;
+
+// Subglottal tract (trachea and bronchi), modelled as a uniform
+// lossy waveguide. It sits below the glottis and is coupled to
+// the vocal tract through the glottal opening (see
+// Tract::enable_trachea). With the glottis closed the two tubes
+// are separate; as it opens, waves pass between them and the
+// subglottal resonances (around 600Hz, 1400Hz and 2200Hz for
+// an adult) show up in the output.
+//
+// Section 0 is the lung end, the last section is just below
+// the glottis. "right" travels upwards, towards the glottis.
+pub struct Trachea {
+    left: Vec<f32>,
+    right: Vec<f32>,
+    junc_left: Vec<f32>,
+    junc_right: Vec<f32>,
+    length: usize,
+    lung_reflection: f32,
+    damping: f32,
+}
+
+impl Trachea {
+    pub fn new(sr: usize, length: f32, oversample: u16) -> Self {
+        let tlen =
+            ((length * 0.01) / (SPEED_OF_SOUND / (sr as f32 * oversample as f32))).floor()
+                + 1.0;
+
+        let tlen = tlen as usize;
+
+        Trachea {
+            left: vec![0.0; tlen],
+            right: vec![0.0; tlen],
+            junc_left: vec![0.0; tlen],
+            junc_right: vec![0.0; tlen],
+            length: tlen,
+            // the lungs are a large, soft cavity: the end
+            // acts mostly like an open, lossy termination
+            lung_reflection: -0.4,
+            damping: 0.995,
+        }
+    }
+
+    // Reflection at the lung end, -1 to 1. Values closer
+    // to zero absorb more and give broader resonances.
+    pub fn set_lung_reflection(&mut self, reflection: f32) {
+        self.lung_reflection = reflection.clamp(-1.0, 1.0);
+    }
+
+    // Per-section loss, 0-1. Subglottal resonances are heavily
+    // damped in real voices.
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    // wave arriving at the glottis from below
+    pub fn upward(&self) -> f32 {
+        self.right[self.length - 1]
+    }
+
+    // Computes the scattering junctions. down is the wave
+    // leaving the tract at the glottis, k is the transmission
+    // through the glottis (0 when closed), sig is the glottal
+    // source, which pulls on the subglottal side.
+    pub(crate) fn scatter(&mut self, down: f32, k: f32, sig: f32) {
+        let len = self.length;
+        let j_l = &mut self.junc_left;
+        let j_r = &mut self.junc_right;
+        let w_l = &self.left;
+        let w_r = &self.right;
+
+        let top_reflection = GLOTTAL_REFLECTION * (1.0 - k);
+        j_l[len - 1] = w_r[len - 1] * top_reflection + k * down - sig;
+        j_r[0] = w_l[0] * self.lung_reflection;
+
+        // uniform tube, no reflections in between
+        j_r[1..len].copy_from_slice(&w_r[..len - 1]);
+        j_l[..len - 1].copy_from_slice(&w_l[1..len]);
+    }
+
+    pub(crate) fn update(&mut self) {
+        let d = self.damping;
+        for i in 0..self.length {
+            self.right[i] = self.junc_right[i] * d;
+            self.left[i] = self.junc_left[i] * d;
+        }
+    }
+
+    // pressure just below the glottis
+    pub fn pressure(&self) -> f32 {
+        self.left[self.length - 1] + self.right[self.length - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GlottalSource;
+    use crate::Rosenberg;
+    use crate::Tract;
+
+    #[test]
+    fn test_coupling() {
+        let sr = 44100;
+        let shape = [1.011, 0.201, 0.487, 0.440, 1.297, 2.368, 1.059, 2.225];
+        let mut glot = Rosenberg::new(sr);
+        let mut plain = Tract::new(sr, 17.0, 1);
+        let mut closed = Tract::new(sr, 17.0, 1);
+        let mut coupled = Tract::new(sr, 17.0, 1);
+
+        for tract in [&mut plain, &mut closed, &mut coupled] {
+            tract.drm(&shape);
+        }
+
+        closed.enable_trachea(14.0);
+        coupled.enable_trachea(14.0);
+        glot.set_freq(220.0);
+
+        let mut diff = 0.0;
+
+        for _ in 0..3 * sr {
+            let g = glot.tick();
+            let out = plain.tick(g);
+
+            // a closed glottis keeps the trachea out of it
+            assert_eq!(out, closed.tick(g));
+
+            coupled.set_glottal_opening(glot.opening());
+            let c = coupled.tick(g);
+            assert!(c.is_finite() && c.abs() < 10.0);
+            diff += (c - out).abs();
+        }
+
+        assert!(diff > 1.0);
+    }
+}
@@ -1,10 +1,15 @@
 use std::f32::consts::PI;
 use crate::Nose;
 use crate::Smoother;
+use crate::Trachea;
+use crate::LinearCongruentialGenerator;
 
-const SPEED_OF_SOUND: f32 = 343.0; /* m/s @ 20C */
+pub(crate) const SPEED_OF_SOUND: f32 = 343.0; /* m/s @ 20C */
 const LIP_REFLECTION: f32 = -0.85;
-const GLOTTAL_REFLECTION: f32 = 0.75;
+pub(crate) const GLOTTAL_REFLECTION: f32 = 0.75;
+
+// transmission through a fully open glottis into the trachea
+const GLOTTAL_TRANSMISSION: f32 = 0.5;
 
 pub struct Tract {
     // TODO: how to use dynbox instead?
@@ -31,6 +36,7 @@ pub struct Tract {
     c1: f32,
     c2: f32,
     yt1: f32,
+    yt1_nasal: f32,
     prvhp: f32,
     tpidsr: f32,
     oversample: u16,
@@ -40,6 +46,17 @@ pub struct Tract {
     tongue_y: f32,
     tongue_smoother_x: Smoother,
     tongue_smoother_y: Smoother,
+
+    // optional subglottal tract, coupled through the glottis
+    trachea: Option<Trachea>,
+    glottal_opening: f32,
+
+    // turbulence noise injected at a constriction,
+    // see set_turbulence
+    turbulence: f32,
+    turbulence_pos: f32,
+    turbulence_rng: LinearCongruentialGenerator,
+    turbulence_prev: f32,
 }
 
 impl Tract {
@@ -62,6 +79,7 @@ impl Tract {
             c2: 0.0,
             hp: 0.0,
             yt1: 0.0,
+            yt1_nasal: 0.0,
             oversample: oversample,
             prvhp: 0.0,
             tpidsr: 2.0 * PI / (sr as f32 * oversample as f32),
@@ -73,6 +91,12 @@ impl Tract {
             tongue_smoother_y: Smoother::new(sr),
             tongue_x: 0.0,
             tongue_y: 0.0,
+            trachea: None,
+            glottal_opening: 0.0,
+            turbulence: 0.0,
+            turbulence_pos: 0.0,
+            turbulence_rng: LinearCongruentialGenerator::new(),
+            turbulence_prev: 0.0,
         };
 
         tr.setup_antialiasing_filter(sr);
@@ -88,6 +112,7 @@ impl Tract {
         self.c2 = b - (b*b - 1.0).sqrt();
         self.c1 = 1.0 - self.c2;
         self.yt1 = 0.0;
+        self.yt1_nasal = 0.0;
     }
 
     pub fn apply_diameters(&mut self) {
@@ -128,7 +153,17 @@ impl Tract {
         let glot_reflection = GLOTTAL_REFLECTION;
         let lip_reflection = LIP_REFLECTION;
 
-        j_r[0] = w_l[0] * glot_reflection + sig;
+        if let Some(trachea) = &mut self.trachea {
+            // the glottis is a leaky junction: the more open
+            // it is, the more passes to and from the trachea
+            let k = self.glottal_opening * GLOTTAL_TRANSMISSION;
+            j_r[0] = w_l[0] * glot_reflection * (1.0 - k) +
+                k * trachea.upward() + sig;
+            trachea.scatter(w_l[0], k, sig);
+        } else {
+            j_r[0] = w_l[0] * glot_reflection + sig;
+        }
+
         j_l[len - 1] = w_r[len - 1] * lip_reflection;
 
         let r = &self.reflections;
@@ -137,6 +172,21 @@ impl Tract {
             j_r[i] = w_r[i - 1] - w;
             j_l[i - 1] = w_l[i] + w;
         }
+
+        if self.turbulence > 0.0 {
+            // white noise, differentiated to tilt it
+            // towards the high end, split between both
+            // directions just past the constriction
+            let noise = self.turbulence_rng.randf() * 2.0 - 1.0;
+            let hp = noise - self.turbulence_prev;
+            self.turbulence_prev = noise;
+
+            let idx = (self.turbulence_pos * len as f32) as usize;
+            let idx = idx.clamp(1, len - 1);
+            let n = hp * self.turbulence * 0.5;
+            j_r[idx] += n;
+            j_l[idx] += n;
+        }
     }
 
     fn update_waveguide(&mut self) {
@@ -149,6 +199,10 @@ impl Tract {
             w_r[i] = j_r[i] * 0.999;
             w_l[i] = j_l[i] * 0.999;
         }
+
+        if let Some(trachea) = &mut self.trachea {
+            trachea.update();
+        }
     }
 
     fn aliasing_suppression(&mut self, sig: f32) -> f32 {
@@ -156,6 +210,12 @@ impl Tract {
         self.yt1
     }
 
+    // same filter as above, with its own state for the nasal channel
+    fn aliasing_suppression_nasal(&mut self, sig: f32) -> f32 {
+        self.yt1_nasal = self.c1*sig + self.c2*self.yt1_nasal;
+        self.yt1_nasal
+    }
+
 
     pub fn tick(&mut self, sig: f32) -> f32 {
         let mut out = 0.0;
@@ -188,7 +248,17 @@ impl Tract {
     }
 
     pub fn tick_with_nose(&mut self, nose: &mut Nose, sig: f32) -> f32 {
-        let mut out = 0.0;
+        let (oral, nasal) = self.tick_with_nose_split(nose, sig);
+        oral + nasal
+    }
+
+    /// Like tick_with_nose, but returns the oral (lip) and nasal
+    /// radiation as separate channels: (oral, nasal). Each channel
+    /// gets its own anti-aliasing filter, so the sum of the two
+    /// is equivalent to tick_with_nose.
+    pub fn tick_with_nose_split(&mut self, nose: &mut Nose, sig: f32) -> (f32, f32) {
+        let mut oral = 0.0;
+        let mut nasal = 0.0;
 
         self.tongue_smoothing();
 
@@ -215,17 +285,17 @@ impl Tract {
             }
 
             self.compute_scattering_junctions(sig);
-            let nasal = nose.tick(self, nose_start);
+            let nose_out = nose.tick(self, nose_start);
             self.update_waveguide();
-            out = self.right[self.tractlen as usize - 1];
-            out += nasal;
+            let lips = self.right[self.tractlen - 1];
 
             // apply crude anti-aliasing filter (simple 1-pole)
-            out = self.aliasing_suppression(out);
+            oral = self.aliasing_suppression(lips);
+            nasal = self.aliasing_suppression_nasal(nose_out);
         }
 
         // TODO: apply nasal component with velum control
-        out
+        (oral, nasal)
     }
     pub fn tongue_shape(&mut self, pos: f32, diam: f32) {
         let pos = pos.clamp(0.0, 1.0);
@@ -243,6 +313,11 @@ impl Tract {
         self.compute_tongue_shape(pos, diam);
     }
 
+    // last tongue position and diameter, see tongue_shape
+    pub fn tongue(&self) -> (f32, f32) {
+        (self.tongue_x, self.tongue_y)
+    }
+
     pub fn set_tongue_smooth(&mut self, smooth: f32) {
         if self.tongue_smooth_amt <= 0.0 {
             self.tongue_smoother_x.snap_to_value(self.tongue_x);
@@ -340,6 +415,45 @@ impl Tract {
         }
     }
 
+    // Adds a subglottal tract below the glottis, length in cm
+    // (about 14cm for an adult). It is coupled to the tract
+    // through the glottal opening, see set_glottal_opening.
+    pub fn enable_trachea(&mut self, length_cm: f32) {
+        self.trachea = Some(Trachea::new(self.sr, length_cm, self.oversample));
+    }
+
+    pub fn disable_trachea(&mut self) {
+        self.trachea = None;
+    }
+
+    pub fn trachea(&mut self) -> Option<&mut Trachea> {
+        self.trachea.as_mut()
+    }
+
+    // How open the glottis is, 0 (closed) to 1 (fully open).
+    // Only matters when the trachea is enabled.
+    pub fn set_glottal_opening(&mut self, opening: f32) {
+        self.glottal_opening = opening.clamp(0.0, 1.0);
+    }
+
+    // Frication noise, as made by air rushing through a narrow
+    // constriction. Level is the noise amplitude, position is
+    // where the noise enters the tract, 0 (glottis) to 1 (lips).
+    pub fn set_turbulence(&mut self, level: f32, position: f32) {
+        self.turbulence = level.max(0.0);
+        self.turbulence_pos = position.clamp(0.0, 1.0);
+    }
+
+    pub fn srand_turbulence(&mut self, seed: u32) {
+        self.turbulence_rng.seed(seed);
+    }
+
+    // acoustic pressure at the glottal end of the tract, the
+    // sum of both travelling waves in the first section
+    pub fn glottal_pressure(&self) -> f32 {
+        self.left[0] + self.right[0]
+    }
+
     pub fn get_lip_reflection(&self) -> f32 {
         return LIP_REFLECTION;
     }
@@ -359,3 +473,41 @@ impl Tract {
         self.tractlen = tractlen;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rosenberg;
+    use crate::GlottalSource;
+
+    #[test]
+    fn test_split_sums_to_combined() {
+        let sr = 44100;
+        let mut glot = Rosenberg::new(sr);
+
+        let mut tract = Tract::new(sr, 17.0, 2);
+        let mut nose = Nose::new(sr, 17.0 * 0.63, 2);
+        let mut split_tract = Tract::new(sr, 17.0, 2);
+        let mut split_nose = Nose::new(sr, 17.0 * 0.63, 2);
+
+        for t in [&mut tract, &mut split_tract] {
+            t.drm(&[1.011, 0.201, 0.487, 0.440, 1.297, 2.368, 1.059, 2.225]);
+        }
+
+        // open the velum, so the nose gets some of the signal
+        nose.set_velum(0.4);
+        split_nose.set_velum(0.4);
+
+        glot.set_freq(150.0);
+        let mut nasal_peak = 0.0f32;
+
+        for _ in 0..sr / 4 {
+            let g = glot.tick();
+            let out = tract.tick_with_nose(&mut nose, g);
+            let (oral, nasal) = split_tract.tick_with_nose_split(&mut split_nose, g);
+            assert_eq!(out, oral + nasal);
+            nasal_peak = nasal_peak.max(nasal.abs());
+        }
+        assert!(nasal_peak > 0.0);
+    }
+}
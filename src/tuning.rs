@@ -0,0 +1,523 @@
+// Tuning subsystem, based on the Scala file formats.
+//
+// A Scale (.scl) is a list of pitches, in cents or as ratios,
+// above an implied 1/1. The last pitch is the period of the
+// scale (usually 2/1, the octave). A KeyboardMapping (.kbm)
+// maps note numbers onto scale degrees, and pins one note to
+// a reference frequency. Together they make up a Tuning,
+// which turns note numbers into frequencies.
+//
+// File format reference: https://www.huygens-fokker.org/scala/scl_format.html
+
+use std::fmt;
+use std::fs;
+
+// midi-to-frequency converter (12-TET, A440)
+pub(crate) fn mtof(nn: f32) -> f32 {
+    (2.0_f32).powf((nn - 69.0) / 12.0) * 440.0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TuningError {
+    Io(String),
+    // line number (1-indexed) and description
+    Parse(usize, String),
+    // the reference note of the keyboard mapping is not mapped
+    UnmappedReference(i32),
+    // a scale needs at least one degree (its period)
+    EmptyScale,
+}
+
+impl fmt::Display for TuningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TuningError::Io(msg) => write!(f, "{}", msg),
+            TuningError::Parse(line, msg) => write!(f, "line {}: {}", line, msg),
+            TuningError::UnmappedReference(note) => {
+                write!(f, "reference note {} is not mapped to a scale degree", note)
+            }
+            TuningError::EmptyScale => write!(f, "scale has no notes"),
+        }
+    }
+}
+
+impl std::error::Error for TuningError {}
+
+// Iterates over the lines that aren't comments, along
+// with their (1-indexed) line numbers.
+fn data_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.starts_with('!'))
+        .map(|(i, l)| (i + 1, l.trim()))
+}
+
+fn read_file(path: &str) -> Result<String, TuningError> {
+    fs::read_to_string(path).map_err(|e| TuningError::Io(format!("{}: {}", path, e)))
+}
+
+#[derive(Debug, Clone)]
+pub struct Scale {
+    pub description: String,
+
+    // degrees 1..=N, in cents. Degree 0 (1/1) is implied.
+    cents: Vec<f32>,
+}
+
+fn parse_pitch(s: &str, line: usize) -> Result<f32, TuningError> {
+    // anything after the first whitespace is a comment
+    let s = s.split_whitespace().next().unwrap_or("");
+
+    if s.contains('.') {
+        return s
+            .parse::<f32>()
+            .map_err(|_| TuningError::Parse(line, format!("invalid cents value '{}'", s)));
+    }
+
+    let (num, den) = match s.split_once('/') {
+        Some((n, d)) => (n, d),
+        None => (s, "1"),
+    };
+
+    let bad_ratio = || TuningError::Parse(line, format!("invalid ratio '{}'", s));
+    let num = num.parse::<u64>().map_err(|_| bad_ratio())?;
+    let den = den.parse::<u64>().map_err(|_| bad_ratio())?;
+
+    if num == 0 || den == 0 {
+        return Err(bad_ratio());
+    }
+
+    Ok(1200.0 * (num as f64 / den as f64).log2() as f32)
+}
+
+impl Scale {
+    pub fn parse(text: &str) -> Result<Scale, TuningError> {
+        let mut lines = data_lines(text);
+
+        // the description may be a blank line, so blank
+        // lines are only skipped after it
+        let description = match lines.next() {
+            Some((_, l)) => l.to_string(),
+            None => return Err(TuningError::Parse(1, "missing description".to_string())),
+        };
+
+        let (line, count) = match lines.next() {
+            Some(l) => l,
+            None => return Err(TuningError::Parse(2, "missing note count".to_string())),
+        };
+
+        let count = count
+            .split_whitespace()
+            .next()
+            .and_then(|c| c.parse::<usize>().ok())
+            .ok_or_else(|| TuningError::Parse(line, "invalid note count".to_string()))?;
+
+        if count == 0 {
+            return Err(TuningError::Parse(line, "scale has no notes".to_string()));
+        }
+
+        let mut cents = Vec::with_capacity(count);
+
+        for (line, l) in lines.filter(|(_, l)| !l.is_empty()).take(count) {
+            cents.push(parse_pitch(l, line)?);
+        }
+
+        if cents.len() < count {
+            return Err(TuningError::Parse(
+                line,
+                format!("expected {} notes, found {}", count, cents.len()),
+            ));
+        }
+
+        Ok(Scale { description, cents })
+    }
+
+    pub fn from_file(path: &str) -> Result<Scale, TuningError> {
+        Scale::parse(&read_file(path)?)
+    }
+
+    // builds a scale from cents values, 1/1 excluded
+    pub fn from_cents(cents: &[f32]) -> Result<Scale, TuningError> {
+        if cents.is_empty() {
+            return Err(TuningError::EmptyScale);
+        }
+
+        Ok(Scale {
+            description: String::new(),
+            cents: cents.to_vec(),
+        })
+    }
+
+    // builds a scale from frequency ratios, 1/1 excluded
+    pub fn from_ratios(ratios: &[f32]) -> Result<Scale, TuningError> {
+        let cents: Vec<f32> = ratios.iter().map(|r| 1200.0 * r.log2()).collect();
+        Scale::from_cents(&cents)
+    }
+
+    // N-tone equal temperament, with a 2/1 period
+    pub fn equal_temperament(n: usize) -> Result<Scale, TuningError> {
+        let step = 1200.0 / n as f32;
+        let cents: Vec<f32> = (1..=n).map(|i| step * i as f32).collect();
+        Scale::from_cents(&cents)
+    }
+
+    // number of degrees per period
+    pub fn len(&self) -> usize {
+        self.cents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cents.is_empty()
+    }
+
+    pub fn period_cents(&self) -> f32 {
+        self.cents[self.cents.len() - 1]
+    }
+
+    // cents above 1/1 of any scale degree, wrapping
+    // into other periods for degrees outside 0..N
+    pub fn degree_cents(&self, degree: i32) -> f32 {
+        let n = self.cents.len() as i32;
+        let period = degree.div_euclid(n);
+        let degree = degree.rem_euclid(n) as usize;
+        let base = if degree == 0 {
+            0.0
+        } else {
+            self.cents[degree - 1]
+        };
+
+        base + period as f32 * self.period_cents()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyboardMapping {
+    pub first_note: i32,
+    pub last_note: i32,
+    pub middle_note: i32,
+    pub reference_note: i32,
+    pub reference_freq: f32,
+    // scale degree treated as the formal octave,
+    // 0 means use the period of the scale
+    pub octave_degree: i32,
+
+    // scale degree for each key in the pattern. None is an
+    // unmapped key. An empty mapping maps keys linearly.
+    pub mapping: Vec<Option<i32>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        KeyboardMapping {
+            // every note is mapped, like plain 12-TET
+            first_note: i32::MIN,
+            last_note: i32::MAX,
+            middle_note: 60,
+            reference_note: 69,
+            reference_freq: 440.0,
+            octave_degree: 0,
+            mapping: vec![],
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn parse(text: &str) -> Result<KeyboardMapping, TuningError> {
+        let mut lines = data_lines(text).filter(|(_, l)| !l.is_empty());
+        let mut last_line = 0;
+
+        let mut field = |name: &str| -> Result<(usize, String), TuningError> {
+            match lines.next() {
+                Some((line, l)) => {
+                    last_line = line;
+                    let value = l.split_whitespace().next().unwrap_or("");
+                    Ok((line, value.to_string()))
+                }
+                None => Err(TuningError::Parse(last_line + 1, format!("missing {}", name))),
+            }
+        };
+
+        let int = |(line, v): (usize, String), name: &str| -> Result<i32, TuningError> {
+            v.parse::<i32>()
+                .map_err(|_| TuningError::Parse(line, format!("invalid {} '{}'", name, v)))
+        };
+
+        let size_field = field("map size")?;
+        let size_line = size_field.0;
+        let size = int(size_field, "map size")?;
+        let first_note = int(field("first note")?, "first note")?;
+        let last_note = int(field("last note")?, "last note")?;
+        let middle_note = int(field("middle note")?, "middle note")?;
+        let reference_note = int(field("reference note")?, "reference note")?;
+
+        let (line, v) = field("reference frequency")?;
+        let reference_freq = v.parse::<f32>().map_err(|_| {
+            TuningError::Parse(line, format!("invalid reference frequency '{}'", v))
+        })?;
+
+        let octave_degree = int(field("octave degree")?, "octave degree")?;
+
+        if size < 0 {
+            return Err(TuningError::Parse(size_line, "negative map size".to_string()));
+        }
+
+        let mut mapping = Vec::with_capacity(size as usize);
+
+        // missing entries at the end are treated as unmapped
+        for _ in 0..size {
+            match field("mapping") {
+                Ok((_, v)) if v == "x" => mapping.push(None),
+                Ok(entry) => mapping.push(Some(int(entry, "mapping")?)),
+                Err(_) => mapping.push(None),
+            }
+        }
+
+        Ok(KeyboardMapping {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_freq,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<KeyboardMapping, TuningError> {
+        KeyboardMapping::parse(&read_file(path)?)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    scale: Scale,
+    kbm: KeyboardMapping,
+    // cents of the reference note, relative to the middle note
+    ref_cents: f32,
+}
+
+impl Default for Tuning {
+    // 12-TET, with A4 (note 69) at 440Hz
+    fn default() -> Self {
+        let scale = Scale::equal_temperament(12).expect("12-TET is not empty");
+        Tuning::new(scale, KeyboardMapping::default()).expect("default tuning is valid")
+    }
+}
+
+impl Tuning {
+    pub fn new(scale: Scale, kbm: KeyboardMapping) -> Result<Tuning, TuningError> {
+        if scale.is_empty() {
+            return Err(TuningError::EmptyScale);
+        }
+
+        let mut tuning = Tuning {
+            scale,
+            kbm,
+            ref_cents: 0.0,
+        };
+
+        let ref_note = tuning.kbm.reference_note;
+        tuning.ref_cents = tuning
+            .key_cents(ref_note)
+            .ok_or(TuningError::UnmappedReference(ref_note))?;
+
+        Ok(tuning)
+    }
+
+    // a scale with the default keyboard mapping
+    // (middle C on the first degree, A4 at 440Hz)
+    pub fn from_scale(scale: Scale) -> Result<Tuning, TuningError> {
+        Tuning::new(scale, KeyboardMapping::default())
+    }
+
+    // loads an .scl file, and optionally a .kbm file
+    pub fn from_files(scl: &str, kbm: Option<&str>) -> Result<Tuning, TuningError> {
+        let scale = Scale::from_file(scl)?;
+        let kbm = match kbm {
+            Some(path) => KeyboardMapping::from_file(path)?,
+            None => KeyboardMapping::default(),
+        };
+        Tuning::new(scale, kbm)
+    }
+
+    pub fn scale(&self) -> &Scale {
+        &self.scale
+    }
+
+    pub fn mapping(&self) -> &KeyboardMapping {
+        &self.kbm
+    }
+
+    fn octave_cents(&self) -> f32 {
+        if self.kbm.octave_degree <= 0 {
+            self.scale.period_cents()
+        } else {
+            self.scale.degree_cents(self.kbm.octave_degree)
+        }
+    }
+
+    // cents of a key relative to the middle note,
+    // None if the key is unmapped or out of range
+    fn key_cents(&self, key: i32) -> Option<f32> {
+        if key < self.kbm.first_note || key > self.kbm.last_note {
+            return None;
+        }
+
+        let offset = key.saturating_sub(self.kbm.middle_note);
+
+        if self.kbm.mapping.is_empty() {
+            return Some(self.scale.degree_cents(offset));
+        }
+
+        let size = self.kbm.mapping.len() as i32;
+        let degree = self.kbm.mapping[offset.rem_euclid(size) as usize]?;
+        let octave = offset.div_euclid(size);
+
+        Some(self.scale.degree_cents(degree) + octave as f32 * self.octave_cents())
+    }
+
+    fn cents_to_freq(&self, cents: f32) -> f32 {
+        self.kbm.reference_freq * (2.0_f32).powf((cents - self.ref_cents) / 1200.0)
+    }
+
+    // frequency of a key, None if it is unmapped
+    pub fn key_freq(&self, key: i32) -> Option<f32> {
+        self.key_cents(key).map(|c| self.cents_to_freq(c))
+    }
+
+    // Frequency of a (possibly fractional) note number.
+    // Fractional notes are interpolated in pitch between
+    // neighbouring keys, so vibrato and glides work in any
+    // scale. Returns None for unmapped keys.
+    pub fn note_to_freq(&self, note: f32) -> Option<f32> {
+        let key = note.floor();
+        let frac = note - key;
+        let key = key as i32;
+        let a = self.key_cents(key)?;
+
+        if frac <= 0.0 {
+            return Some(self.cents_to_freq(a));
+        }
+
+        let cents = match self.key_cents(key.saturating_add(1)) {
+            Some(b) => a + (b - a) * frac,
+            None => a,
+        };
+
+        Some(self.cents_to_freq(cents))
+    }
+
+    // Frequency of a scale degree counted from the middle note,
+    // bypassing the keyboard mapping. Degrees can be negative,
+    // and octave adds whole periods of the scale.
+    pub fn degree_freq(&self, degree: i32, octave: i32) -> f32 {
+        let n = self.scale.len() as i32;
+        self.cents_to_freq(self.scale.degree_cents(degree + octave * n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEANTONE: &str = "! meanquar.scl
+!
+1/4-comma meantone scale. Pietro Aaron's temperament (1523)
+ 12
+!
+ 76.04900
+ 193.15686
+ 310.26471
+ 5/4
+ 503.42157
+ 579.47057
+ 696.57843
+ 25/16
+ 889.73529
+ 1006.84314
+ 1082.89214
+ 2/1
+";
+
+    #[test]
+    fn test_default_matches_mtof() {
+        let tuning = Tuning::default();
+
+        // no range limit, like mtof
+        for nn in [-12.0, 0.0, 21.0, 60.0, 69.0, 60.5, 127.0, 127.5, 140.0] {
+            let f = tuning.note_to_freq(nn).unwrap();
+            assert!((f - mtof(nn)).abs() < 0.01 * f, "mismatch at note {}", nn);
+        }
+    }
+
+    #[test]
+    fn test_parse_scale() {
+        let scale = Scale::parse(MEANTONE).unwrap();
+        assert_eq!(scale.len(), 12);
+        assert!(scale.description.starts_with("1/4-comma meantone"));
+        assert!((scale.degree_cents(4) - 386.3137).abs() < 0.001);
+        assert!((scale.degree_cents(12) - 1200.0).abs() < 0.001);
+        assert!((scale.degree_cents(-8) - (386.3137 - 1200.0)).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_parse_scale_errors() {
+        let err = Scale::parse("bad\n3\n100.0\n3/0\n").unwrap_err();
+        assert_eq!(err, TuningError::Parse(4, "invalid ratio '3/0'".to_string()));
+
+        let err = Scale::parse("short\n3\n100.0\n").unwrap_err();
+        assert!(matches!(err, TuningError::Parse(2, _)));
+
+        assert_eq!(Scale::from_cents(&[]).unwrap_err(), TuningError::EmptyScale);
+        assert_eq!(Scale::equal_temperament(0).unwrap_err(), TuningError::EmptyScale);
+    }
+
+    #[test]
+    fn test_keyboard_mapping() {
+        // pentatonic on the white keys, black keys unmapped
+        let kbm = KeyboardMapping::parse(
+            "! test
+12
+0
+127
+60
+69
+440.0
+5
+0
+x
+1
+x
+2
+x
+x
+3
+x
+4
+x
+",
+        )
+        .unwrap();
+
+        let scale = Scale::from_ratios(&[9.0 / 8.0, 5.0 / 4.0, 3.0 / 2.0, 5.0 / 3.0, 2.0]).unwrap();
+        let tuning = Tuning::new(scale, kbm).unwrap();
+
+        // reference note A4 is degree 4 (5/3)
+        assert!((tuning.key_freq(69).unwrap() - 440.0).abs() < 0.001);
+        assert!((tuning.key_freq(60).unwrap() - 264.0).abs() < 0.001);
+        assert!((tuning.key_freq(72).unwrap() - 528.0).abs() < 0.001);
+        assert!(tuning.key_freq(61).is_none());
+
+        // the last key in the pattern is past the end of the
+        // mapping entries, so it's unmapped as well
+        assert!(tuning.key_freq(71).is_none());
+    }
+
+    #[test]
+    fn test_degrees() {
+        let tuning = Tuning::from_scale(Scale::parse(MEANTONE).unwrap()).unwrap();
+        let c4 = tuning.degree_freq(0, 0);
+        let e5 = tuning.degree_freq(4, 1);
+        assert!((e5 / c4 - 2.5).abs() < 0.001);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::GlottalSource;
+
+// Self-oscillating two-mass model of the vocal folds, after
+// Ishizaka and Flanagan (1972), using the simplified pressure
+// and collision model from Steinecke and Herzel (1995).
+//
+// Each fold is represented by a lower mass (m1) and an upper
+// mass (m2), coupled by a spring (kc). The masses are driven by
+// the subglottal pressure acting on the lower mass. The flow
+// through the narrowest opening follows Bernoulli. Unlike the
+// other glottal sources, pitch is not imposed but comes out of
+// the oscillation; set_freq adjusts the fold tension (Q) to
+// approximate the requested frequency.
+//
+// Units are SI (kg, m, s, Pa).
+
+const AIR_DENSITY: f32 = 1.13;
+
+// oscillation frequency at Q = 1 with 800Pa of subglottal
+// pressure, approximately. Higher tensions need more pressure
+// to keep oscillating.
+const BASE_FREQ: f32 = 150.0;
+
+// internal oversampling, needed for stable integration
+// of the collision springs at high tensions
+const SUBSTEPS: usize = 4;
+
+// source-tract coupling, same meaning as in Glot
+const LOAD_LEAK: f32 = 0.995;
+const LOAD_SCALE: f32 = 4.0;
+
+pub struct TwoMass {
+    dt: f32,
+    sr: f32,
+
+    // displacements and velocities
+    x1: f32,
+    v1: f32,
+    x2: f32,
+    v2: f32,
+
+    m1: f32,
+    m2: f32,
+    k1: f32,
+    k2: f32,
+    kc: f32,
+
+    // rest areas, fold length, thickness of the lower mass.
+    // The upper mass sees no pressure in this model, so its
+    // thickness is not needed.
+    a01: f32,
+    a02: f32,
+    length: f32,
+    d1: f32,
+
+    // tension parameter
+    q: f32,
+
+    // subglottal pressure
+    ps: f32,
+
+    // source-tract interaction amount, 0-1
+    coupling: f32,
+    load_pressure: f32,
+
+    // requested frequency, used to scale the tract load
+    freq: f32,
+
+    flow: f32,
+
+    // scaling for the flow derivative output
+    norm: f32,
+
+    // Period tracking. There is no phasor here, so periods are
+    // found from the flow: a period starts when the flow begins
+    // to rise, and closure is the flow minimum, when it stops
+    // falling (whether or not the folds fully close). The phase
+    // is estimated from the length of the previous period.
+    pdiff: f32,
+    count: usize,
+    period_len: usize,
+    period_trig: f32,
+    closure_trig: f32,
+
+    // glottal area, and its peak over the current and
+    // previous period, for the normalized opening
+    area: f32,
+    area_max: f32,
+    area_peak: f32,
+}
+
+impl TwoMass {
+    pub fn new(sr: usize) -> Self {
+        let mut tm = TwoMass {
+            sr: sr as f32,
+            dt: 1.0 / (sr as f32 * SUBSTEPS as f32),
+            // small initial displacement to kick off the oscillation,
+            // the symmetric rest position is an equilibrium
+            x1: 1e-4,
+            v1: 0.0,
+            x2: 0.0,
+            v2: 0.0,
+            m1: 0.125e-3,
+            m2: 0.025e-3,
+            k1: 80.0,
+            k2: 8.0,
+            kc: 25.0,
+            a01: 0.05e-4,
+            a02: 0.05e-4,
+            length: 1.4e-2,
+            d1: 0.25e-2,
+            q: 1.0,
+            ps: 800.0,
+            coupling: 0.0,
+            load_pressure: 0.0,
+            freq: 140.0,
+            flow: 0.0,
+            norm: 1.0,
+            pdiff: 0.0,
+            count: 0,
+            period_len: 1,
+            period_trig: 0.0,
+            closure_trig: 0.0,
+            area: 0.0,
+            area_max: 0.0,
+            area_peak: 1e-6,
+        };
+
+        tm.set_freq(140.0);
+
+        // start with the flow already established, otherwise
+        // the first sample is a large step
+        let amin = (tm.a01 + 2.0 * tm.length * tm.x1).min(tm.a02);
+        tm.flow = (2.0 * tm.ps / AIR_DENSITY).sqrt() * amin;
+        tm
+    }
+
+    // subglottal (lung) pressure, in Pascals. Below roughly
+    // 300Pa the folds will not oscillate.
+    pub fn set_subglottal_pressure(&mut self, ps: f32) {
+        self.ps = ps.max(0.0);
+    }
+
+    // vocal fold tension. Stiffness is multiplied by Q, mass is
+    // divided by it, so the natural frequency scales with Q.
+    pub fn set_tension(&mut self, q: f32) {
+        self.q = q.max(0.1);
+    }
+
+    // glottal rest area (both masses), in square centimeters.
+    // A larger rest area gives a breathier, leakier voice.
+    pub fn set_rest_area(&mut self, area_cm2: f32) {
+        self.a01 = area_cm2 * 1e-4;
+        self.a02 = area_cm2 * 1e-4;
+    }
+
+    // current glottal flow, in m^3/s
+    pub fn flow(&self) -> f32 {
+        self.flow
+    }
+
+    // dp is the pressure drop across the glottis
+    fn step(&mut self, dp: f32) -> f32 {
+        let q = self.q;
+        let m1 = self.m1 / q;
+        let m2 = self.m2 / q;
+        let k1 = self.k1 * q;
+        let k2 = self.k2 * q;
+        let kc = self.kc * q;
+        let l = self.length;
+
+        let a1 = self.a01 + 2.0 * l * self.x1;
+        let a2 = self.a02 + 2.0 * l * self.x2;
+        let amin = a1.min(a2);
+        self.area = amin.max(0.0);
+
+        let p1 = if a1 <= 0.0 {
+            0.0
+        } else if amin <= 0.0 {
+            dp
+        } else {
+            dp * (1.0 - (amin / a1) * (amin / a1))
+        };
+
+        // collision adds stiffness (3x) and extra damping
+        let (c1, z1) = if a1 < 0.0 { (3.0 * k1, 1.1) } else { (0.0, 0.1) };
+        let (c2, z2) = if a2 < 0.0 { (3.0 * k2, 1.1) } else { (0.0, 0.1) };
+        let r1 = 2.0 * z1 * (m1 * k1).sqrt();
+        let r2 = 2.0 * z2 * (m2 * k2).sqrt();
+
+        let f1 = l * self.d1 * p1
+            - r1 * self.v1
+            - k1 * self.x1
+            - c1 * a1.min(0.0) / (2.0 * l)
+            - kc * (self.x1 - self.x2);
+
+        let f2 = -r2 * self.v2
+            - k2 * self.x2
+            - c2 * a2.min(0.0) / (2.0 * l)
+            - kc * (self.x2 - self.x1);
+
+        // semi-implicit Euler
+        self.v1 += self.dt * f1 / m1;
+        self.v2 += self.dt * f2 / m2;
+        self.x1 += self.dt * self.v1;
+        self.x2 += self.dt * self.v2;
+
+        if amin > 0.0 {
+            (2.0 * dp / AIR_DENSITY).sqrt() * amin
+        } else {
+            0.0
+        }
+    }
+}
+
+impl GlottalSource for TwoMass {
+    fn set_freq(&mut self, freq: f32) {
+        self.freq = freq;
+        self.set_tension(freq / BASE_FREQ);
+
+        // the flow derivative grows with frequency, so
+        // normalize it to keep the output level steady
+        self.norm = 1.0 / (freq.max(1.0) * 3.6e-3);
+    }
+
+    fn tick(&mut self) -> f32 {
+        self.tick_with_load(0.0)
+    }
+
+    fn set_subglottal_pressure(&mut self, pressure: f32) {
+        TwoMass::set_subglottal_pressure(self, pressure)
+    }
+
+    fn set_coupling(&mut self, coupling: f32) {
+        self.coupling = coupling.clamp(0.0, 1.0);
+    }
+
+    // The tract is driven by the flow derivative, so its
+    // pressure is integrated (over normalized time) first. It is
+    // in arbitrary units, so it is taken relative to the
+    // subglottal pressure: both the force on the folds and the
+    // flow see the reduced drop.
+    fn tick_with_load(&mut self, pressure: f32) -> f32 {
+        let pflow = self.flow;
+        self.load_pressure =
+            self.load_pressure * LOAD_LEAK + pressure * self.freq / self.sr;
+        let p = self.coupling * LOAD_SCALE * self.load_pressure;
+        let dp = self.ps * (1.0 - p).max(0.0);
+
+        for _ in 0..SUBSTEPS {
+            self.flow = self.step(dp);
+        }
+
+        let diff = self.flow - pflow;
+
+        self.period_trig = 0.0;
+        self.closure_trig = 0.0;
+        self.count += 1;
+
+        // Folds that close leave the flow flat at 0, so the
+        // minimum comes before the next rise. Leaky ones turn
+        // straight around, and both fire on the same tick.
+        if self.pdiff < 0.0 && diff >= 0.0 {
+            self.closure_trig = 1.0;
+        }
+
+        if self.pdiff <= 0.0 && diff > 0.0 {
+            self.period_trig = 1.0;
+            self.period_len = self.count;
+            self.count = 0;
+            self.area_peak = self.area_max.max(1e-6);
+            self.area_max = 0.0;
+        }
+
+        self.pdiff = diff;
+        self.area_max = self.area_max.max(self.area);
+
+        diff * self.sr * self.norm
+    }
+
+    fn period_trigger(&self) -> f32 {
+        self.period_trig
+    }
+
+    fn closure_trigger(&self) -> f32 {
+        self.closure_trig
+    }
+
+    fn phase(&self) -> f32 {
+        (self.count as f32 / self.period_len as f32).min(0.999)
+    }
+
+    fn opening(&self) -> f32 {
+        (self.area / self.area_peak).min(1.0)
+    }
+}
@@ -0,0 +1,214 @@
+use crate::Jitter;
+use crate::Phasor;
+use std::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VibratoShape {
+    Sine,
+    Triangle,
+    // triangle with flattened peaks, closer to how some
+    // singers linger at the extremes
+    Trapezoid,
+}
+
+// Vibrato LFO for Voice, in semitones.
+//
+// Like a singer's vibrato, it can start a little after the
+// note (onset delay), fade in, and drift in rate and depth.
+// Call onset at the start of each note to restart it.
+pub struct Vibrato {
+    phasor: Phasor,
+    onedsr: f32,
+    rate: f32,
+    depth: f32,
+    shape: VibratoShape,
+
+    // seconds since the last onset, delay before the
+    // vibrato starts, and fade-in time after that
+    time: f32,
+    delay: f32,
+    fade: f32,
+
+    // amount of rate and depth drift, relative to the
+    // rate and depth (0-1)
+    rate_jitter: f32,
+    depth_jitter: f32,
+    rate_drift: Jitter,
+    depth_drift: Jitter,
+}
+
+impl Vibrato {
+    pub fn new(sr: usize) -> Self {
+        let mut rate_drift = Jitter::new(sr);
+        rate_drift.range_rate(0.5, 2.0);
+        rate_drift.range_amplitude(-1.0, 1.0);
+        rate_drift.seed(1, 2);
+
+        let mut depth_drift = Jitter::new(sr);
+        depth_drift.range_rate(0.3, 1.5);
+        depth_drift.range_amplitude(-1.0, 1.0);
+        depth_drift.seed(3, 4);
+
+        let mut vib = Vibrato {
+            phasor: Phasor::new(sr, 0.0),
+            onedsr: 1.0 / sr as f32,
+            rate: 6.0,
+            depth: 0.03,
+            shape: VibratoShape::Sine,
+            time: 0.0,
+            delay: 0.0,
+            fade: 0.0,
+            rate_jitter: 0.0,
+            depth_jitter: 0.0,
+            rate_drift,
+            depth_drift,
+        };
+
+        vib.phasor.set_freq(vib.rate);
+        vib
+    }
+
+    // rate, in Hz
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+        self.phasor.set_freq(rate);
+    }
+
+    // depth, in semitones
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth;
+    }
+
+    pub fn set_shape(&mut self, shape: VibratoShape) {
+        self.shape = shape;
+    }
+
+    // time (in seconds) after the onset before the vibrato
+    // starts, and how long it then takes to reach full depth
+    pub fn set_onset(&mut self, delay: f32, fade: f32) {
+        self.delay = delay.max(0.0);
+        self.fade = fade.max(0.0);
+    }
+
+    // random drift in rate and depth, 0-1 (relative)
+    pub fn set_rate_jitter(&mut self, amount: f32) {
+        self.rate_jitter = amount.clamp(0.0, 1.0);
+    }
+
+    pub fn set_depth_jitter(&mut self, amount: f32) {
+        self.depth_jitter = amount.clamp(0.0, 1.0);
+    }
+
+    pub fn seed(&mut self, seed: u32) {
+        self.rate_drift.seed(seed, seed.wrapping_add(1));
+        self.depth_drift.seed(seed.wrapping_add(2), seed.wrapping_add(3));
+    }
+
+    // restarts the vibrato, with its onset delay and fade
+    pub fn onset(&mut self) {
+        self.phasor.reset();
+        self.time = 0.0;
+    }
+
+    fn shape(&self, phs: f32) -> f32 {
+        match self.shape {
+            VibratoShape::Sine => (phs * 2.0*PI).sin(),
+            VibratoShape::Triangle => triangle(phs),
+            VibratoShape::Trapezoid => (triangle(phs) * 1.5).clamp(-1.0, 1.0),
+        }
+    }
+
+    // how far into the onset fade we are, 0-1
+    fn fade_gain(&self) -> f32 {
+        let t = self.time - self.delay;
+
+        if t < 0.0 {
+            0.0
+        } else if t < self.fade {
+            t / self.fade
+        } else {
+            1.0
+        }
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        if self.rate_jitter > 0.0 {
+            let drift = self.rate_drift.tick() * self.rate_jitter;
+            self.phasor.set_freq(self.rate * (1.0 + drift));
+        }
+
+        let mut depth = self.depth;
+
+        if self.depth_jitter > 0.0 {
+            depth *= 1.0 + self.depth_drift.tick() * self.depth_jitter;
+        }
+
+        let phs = self.phasor.tick();
+        let gain = self.fade_gain();
+
+        // stop counting once the fade is done, so the
+        // time doesn't lose precision on long notes
+        if gain < 1.0 {
+            self.time += self.onedsr;
+        }
+
+        self.shape(phs) * depth * gain
+    }
+}
+
+// triangle wave, starting at 0 and rising like sine
+fn triangle(phs: f32) -> f32 {
+    if phs < 0.25 {
+        4.0 * phs
+    } else if phs < 0.75 {
+        2.0 - 4.0 * phs
+    } else {
+        4.0 * phs - 4.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_onset() {
+        let sr = 1000;
+        let mut vib = Vibrato::new(sr);
+        vib.set_depth(1.0);
+        vib.set_rate(5.0);
+        vib.set_onset(0.1, 0.2);
+        vib.onset();
+
+        // silent during the delay
+        for _ in 0..100 {
+            assert_eq!(vib.tick(), 0.0);
+        }
+
+        // then fades in: one cycle peaks below full depth
+        let peak = (0..200).map(|_| vib.tick().abs()).fold(0.0, f32::max);
+        assert!(peak > 0.1 && peak < 1.0);
+
+        let peak = (0..200).map(|_| vib.tick().abs()).fold(0.0, f32::max);
+        assert!(peak > 0.99);
+
+        // onset starts it over
+        vib.onset();
+        assert_eq!(vib.tick(), 0.0);
+    }
+
+    #[test]
+    fn test_shapes() {
+        for shape in [VibratoShape::Triangle, VibratoShape::Trapezoid] {
+            let mut vib = Vibrato::new(1000);
+            vib.set_rate(1.0);
+            vib.set_depth(1.0);
+            vib.set_shape(shape);
+
+            let out: Vec<f32> = (0..1000).map(|_| vib.tick()).collect();
+            assert_eq!(out[0], 0.0);
+            assert!((out[250] - 1.0).abs() < 1e-3);
+            assert!((out[750] + 1.0).abs() < 1e-3);
+        }
+    }
+}
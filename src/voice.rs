@@ -1,57 +1,370 @@
+use crate::Balloon;
+use crate::Envelope;
 use crate::Tract;
 use crate::Glot;
+use crate::GlottalSource;
 use crate::Nose;
-use crate::Phasor;
-use std::f32::consts::PI;
+use crate::Portamento;
+use crate::Tuning;
+use crate::VoicePreset;
+use crate::Vibrato;
 
-pub struct Voice {
+// default nose length, relative to the tract
+const NOSE_RATIO: f32 = 0.63;
+
+// the lung pressure is passed on to the glottis once
+// every this many samples (it changes slowly)
+const LUNG_CONTROL_PERIOD: u32 = 32;
+
+// subglottal pressure range for note velocity, in Pascals
+const PRESSURE_SOFT: f32 = 400.0;
+const PRESSURE_LOUD: f32 = 1600.0;
+
+pub struct Voice<G: GlottalSource = Glot> {
     pub tract: Tract,
-    pub glottis: Glot,
+    pub glottis: G,
     pub nose: Nose,
     pub pitch: f32,
-    phasor: Phasor,
-    vibdepth: f32,
+    // pitch bend, in semitones, added to pitch
+    pub bend: f32,
+    pub vibrato: Vibrato,
+    pub portamento: Portamento,
+    tuning: Tuning,
+    nose_ratio: f32,
+
+    // optional lung model driving the subglottal pressure,
+    // and the pressure (in Pa) it gives when fully inflated
+    pub lungs: Option<Balloon>,
+    lung_pressure: f32,
+    lung_counter: u32,
+
+    // notes held down (note, velocity), most recent last
+    held: Vec<(f32, f32)>,
+
+    // Note gate, applied to the glottal source output so it
+    // works the same with any source, and lets the tract ring
+    // out. Only used once note_on has been called.
+    env: Envelope,
+    gate: f32,
+    gated: bool,
 }
 
-impl Voice {
+impl Voice<Glot> {
     pub fn new(sr: usize, length_cm: f32, oversample: u16) -> Self {
+        let mut v = Voice::with_source(sr, length_cm, oversample, Glot::new(sr));
+        let preset = VoicePreset::default();
+
+        v.glottis.set_shape(preset.shape);
+        v.glottis.set_aspiration(preset.aspiration);
+        v.glottis.set_noise_floor(preset.noise_floor);
+        v
+    }
+
+    // whisper amount, 0 (voiced) to 1 (fully whispered)
+    pub fn set_whisper(&mut self, whisper: f32) {
+        self.glottis.set_whisper(whisper);
+    }
+}
+
+impl<G: GlottalSource> Voice<G> {
+    // creates a voice with an arbitrary glottal source model
+    pub fn with_source(sr: usize, length_cm: f32, oversample: u16, glottis: G) -> Self {
+        Voice::with_nose_ratio(sr, length_cm, NOSE_RATIO, oversample, glottis)
+    }
+
+    // like with_source, with the nose length given relative
+    // to the tract length
+    pub fn with_nose_ratio(
+        sr: usize,
+        length_cm: f32,
+        nose_ratio: f32,
+        oversample: u16,
+        glottis: G
+    ) -> Self {
         let mut v = Voice {
             tract: Tract::new(sr, length_cm, oversample),
-            glottis: Glot::new(sr),
-            nose: Nose::new(sr, length_cm * 0.63, oversample),
-            phasor: Phasor::new(sr, 0.0),
+            glottis,
+            nose: Nose::new(sr, length_cm * nose_ratio, oversample),
+            vibrato: Vibrato::new(sr),
+            portamento: Portamento::new(sr),
             pitch: 60.0,
-            vibdepth: 0.03,
+            bend: 0.0,
+            tuning: Tuning::default(),
+            nose_ratio,
+            lungs: None,
+            lung_pressure: 1000.0,
+            lung_counter: 0,
+            held: vec![],
+            env: Envelope::new(sr),
+            gate: 0.0,
+            gated: false,
         };
 
-        v.glottis.set_shape(0.476);
-        v.glottis.set_aspiration(0.1);
-        v.glottis.set_noise_floor(0.287);
-        v.phasor.set_freq(6.0);
+        v.env.set_attack(0.02);
+        v.env.set_release(0.15);
+        v.vibrato.set_rate(6.0);
+        v.vibrato.set_depth(0.03);
         v
     }
 
+    // pitches (including gesture output) are note numbers,
+    // resolved to frequencies through this tuning
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+    }
+
+    // Subglottal pressure, in Pascals. For Glot this drives
+    // amplitude, voice quality, breath noise and voicing (around
+    // 300Pa and up to start phonation, 800Pa for normal speech).
+    pub fn set_pressure(&mut self, pressure: f32) {
+        self.glottis.set_subglottal_pressure(pressure);
+    }
+
+    // Drives the pressure from a lung model instead: the
+    // balloon volume (0-1) is scaled by max_pressure (Pa), and
+    // passed on at control rate (see LUNG_CONTROL_PERIOD).
+    pub fn set_lungs(&mut self, lungs: Balloon, max_pressure: f32) {
+        self.lungs = Some(lungs);
+        self.lung_pressure = max_pressure;
+        self.lung_counter = 0;
+    }
+
+    pub fn remove_lungs(&mut self) {
+        self.lungs = None;
+    }
+
+    // how hard the lungs push (the balloon pressure), 0 stops
+    // the airflow. Does nothing without lungs.
+    pub fn set_breath(&mut self, breath: f32) {
+        if let Some(lungs) = &mut self.lungs {
+            lungs.pressure = breath;
+        }
+    }
+
     pub fn vibrato_rate(&mut self, rate: f32) {
-        self.phasor.set_freq(rate);
+        self.vibrato.set_rate(rate);
     }
 
     pub fn vibrato_depth(&mut self, depth: f32) {
-        self.vibdepth = depth;
+        self.vibrato.set_depth(depth);
+    }
+
+    // marks the start of a new note, restarting the vibrato
+    // (with its onset delay and fade-in)
+    pub fn onset(&mut self) {
+        self.vibrato.onset();
+    }
+
+    // glide time between legato notes, in milliseconds
+    pub fn set_portamento(&mut self, ms: f32) {
+        self.portamento.set_time(ms);
+    }
+
+    // note gate attack and release times, in seconds
+    pub fn set_attack(&mut self, time: f32) {
+        self.env.set_attack(time);
+    }
+
+    pub fn set_release(&mut self, time: f32) {
+        self.env.set_release(time);
+    }
+
+    // Starts a note, opening the note gate. Velocity (0-1) sets
+    // the subglottal pressure, which sources that model it (Glot,
+    // TwoMass) turn into loudness and voice quality. With lungs
+    // attached the lungs drive the pressure instead, and velocity
+    // is ignored (see set_breath). If another note is still held,
+    // this is legato: the pitch glides over (see Portamento) and
+    // the vibrato carries on. Otherwise the note starts afresh.
+    pub fn note_on(&mut self, note: f32, velocity: f32) {
+        let legato = !self.held.is_empty();
+
+        self.held.retain(|n| n.0 != note);
+        self.held.push((note, velocity));
+        self.velocity_to_pressure(velocity);
+        self.gated = true;
+        self.gate = 1.0;
+
+        if legato {
+            self.glide_to(note);
+        } else {
+            self.portamento.stop();
+            self.pitch = note;
+            self.onset();
+        }
+    }
+
+    // Releases a note. If it was the one sounding and others
+    // are still held, glides back to the last one of them.
+    // Releasing the last note closes the note gate.
+    pub fn note_off(&mut self, note: f32) {
+        let sounding = self.held.last().map(|n| n.0) == Some(note);
+        self.held.retain(|n| n.0 != note);
+
+        if !sounding {
+            return;
+        }
+
+        match self.held.last() {
+            Some(&(prev, velocity)) => {
+                self.velocity_to_pressure(velocity);
+                self.glide_to(prev);
+            }
+            None => self.gate = 0.0,
+        }
+    }
+
+    // the lungs, when attached, would overwrite this
+    // within LUNG_CONTROL_PERIOD samples
+    fn velocity_to_pressure(&mut self, velocity: f32) {
+        if self.lungs.is_none() {
+            self.set_pressure(velocity_pressure(velocity));
+        }
+    }
+
+    fn glide_to(&mut self, note: f32) {
+        self.portamento.glide(self.pitch, note);
+
+        // no portamento time
+        if !self.portamento.is_active() {
+            self.pitch = note;
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        self.held.clear();
+        self.gate = 0.0;
+    }
+
+    // number of notes held down
+    pub fn notes_held(&self) -> usize {
+        self.held.len()
     }
 
     pub fn tick(&mut self) -> f32 {
-        let phs = self.phasor.tick();
-        let vib = (phs * 2.0*PI).sin() * self.vibdepth;
-        self.glottis.set_pitch(self.pitch + vib);
-        let g = self.glottis.tick();
-        // TODO: use with nose
-        let t = self.tract.tick_with_nose(&mut self.nose, g);
-        t
+        let (oral, nasal) = self.tick_split();
+        oral + nasal
+    }
+
+    /// Computes the next sample with the mouth and nose
+    /// outputs kept apart: (oral, nasal).
+    pub fn tick_split(&mut self) -> (f32, f32) {
+        if let Some(lungs) = &mut self.lungs {
+            let volume = lungs.tick();
+
+            if self.lung_counter == 0 {
+                self.glottis.set_subglottal_pressure(volume * self.lung_pressure);
+            }
+
+            self.lung_counter = (self.lung_counter + 1) % LUNG_CONTROL_PERIOD;
+        }
+
+        if let Some(pitch) = self.portamento.tick() {
+            self.pitch = pitch;
+        }
+
+        let vib = self.vibrato.tick();
+        if let Some(freq) = self.tuning.note_to_freq(self.pitch + self.bend + vib) {
+            self.glottis.set_freq(freq);
+        }
+        let mut g = self.glottis.tick_with_load(self.tract.glottal_pressure());
+
+        if self.gated {
+            g *= self.env.tick(self.gate);
+        }
+
+        self.tract.set_glottal_opening(self.glottis.opening());
+        self.tract.tick_with_nose_split(&mut self.nose, g)
+    }
+
+    // Amount of interaction between the glottal source and
+    // the tract (0-1). At 0, the source ignores the tract.
+    pub fn set_coupling(&mut self, coupling: f32) {
+        self.glottis.set_coupling(coupling);
+    }
+
+    // glottal period start and closure triggers from the
+    // last tick, for pitch-synchronous processing
+    pub fn period_trigger(&self) -> f32 {
+        self.glottis.period_trigger()
+    }
+
+    pub fn closure_trigger(&self) -> f32 {
+        self.glottis.closure_trigger()
+    }
+
+    // position in the current glottal period, 0-1
+    pub fn glottal_phase(&self) -> f32 {
+        self.glottis.phase()
     }
 
     pub fn set_length(&mut self, len_cm: f32) {
         self.tract.set_length(len_cm);
-        self.nose.set_length(len_cm*0.63);
+        self.nose.set_length(len_cm*self.nose_ratio);
     }
 }
 
+fn velocity_pressure(velocity: f32) -> f32 {
+    let v = velocity.clamp(0.0, 1.0);
+    PRESSURE_SOFT * (PRESSURE_LOUD / PRESSURE_SOFT).powf(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rosenberg;
+
+    #[test]
+    fn test_note_off_silences() {
+        let sr = 44100;
+        let mut voice = Voice::with_source(sr, 17.0, 1, Rosenberg::new(sr));
+
+        let peak = |voice: &mut Voice<Rosenberg>, n: usize| {
+            (0..n).map(|_| voice.tick().abs()).fold(0.0, f32::max)
+        };
+
+        voice.note_on(60.0, 0.8);
+        assert!(peak(&mut voice, sr / 2) > 0.01);
+
+        // Rosenberg ignores pressure, the note gate doesn't
+        voice.note_off(60.0);
+        peak(&mut voice, sr / 2);
+        assert!(peak(&mut voice, sr / 10) < 1e-4);
+
+        // and opens again for the next note
+        voice.note_on(62.0, 0.8);
+        assert!(peak(&mut voice, sr / 2) > 0.01);
+        voice.all_notes_off();
+        peak(&mut voice, sr / 2);
+        assert!(peak(&mut voice, sr / 10) < 1e-4);
+    }
+
+    #[test]
+    fn test_breath() {
+        let sr = 44100;
+        let mut voice = Voice::new(sr, 17.0, 1);
+
+        let peak = |voice: &mut Voice, n: usize| {
+            (0..n).map(|_| voice.tick().abs()).fold(0.0, f32::max)
+        };
+
+        voice.set_lungs(Balloon::new(sr), 1000.0);
+        voice.set_breath(1.0);
+        peak(&mut voice, sr);
+        let voiced = peak(&mut voice, sr / 10);
+        assert!(voiced > 0.01);
+
+        // out of breath: the voice stops, with no note gate
+        voice.set_breath(0.0);
+        peak(&mut voice, 3 * sr);
+        let silent = peak(&mut voice, sr / 10);
+        assert!(!voice.glottis.is_voiced());
+        assert!(silent < 1e-3 * voiced);
+
+        // and starts again with the next breath
+        voice.set_breath(1.0);
+        peak(&mut voice, sr);
+        assert!(voice.glottis.is_voiced());
+        assert!(peak(&mut voice, sr / 10) > 0.5 * voiced);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::Envelope;
+use crate::Glot;
+use crate::GlottalSource;
+use crate::LinearCongruentialGenerator;
+use crate::Voice;
+use crate::VoiceBuilder;
+use crate::VoicePreset;
+
+// below this, a released voice counts as silent
+const SILENCE: f32 = 1e-4;
+
+// What to do when a note comes in and every voice is busy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StealPolicy {
+    // take the voice that started longest ago
+    Oldest,
+    // take the voice with the lowest envelope level
+    Quietest,
+    // take the voice playing the lowest/highest note
+    Lowest,
+    Highest,
+    // drop the new note
+    None,
+}
+
+struct BankVoice<G: GlottalSource> {
+    voice: Voice<G>,
+    env: Envelope,
+    gate: f32,
+    level: f32,
+    velocity: f32,
+
+    // note being played (None when released), the order
+    // it was started in, and the pitch to use at the onset
+    // (the note with a random detune)
+    note: Option<f32>,
+    stamp: u64,
+    pitch: f32,
+
+    // humanized timing: samples left before the gate
+    // turns on or off
+    pending_on: Option<usize>,
+    pending_off: Option<usize>,
+}
+
+impl<G: GlottalSource> BankVoice<G> {
+    fn is_idle(&self) -> bool {
+        self.note.is_none() && self.pending_on.is_none() && self.level < SILENCE
+    }
+}
+
+// A set of voices played like a polyphonic instrument:
+// note_on picks a voice (stealing one if needed), note_off
+// releases it. Each voice has its own envelope, a small random
+// detune and optional timing humanization, so that a chord
+// doesn't sound like copies of the same voice.
+pub struct VoiceBank<G: GlottalSource = Glot> {
+    voices: Vec<BankVoice<G>>,
+    stems: Vec<f32>,
+    policy: StealPolicy,
+    rng: LinearCongruentialGenerator,
+    sr: usize,
+    stamp: u64,
+    detune: f32,
+    humanize: f32,
+}
+
+impl VoiceBank<Glot> {
+    // nvoices copies of a preset. Each voice gets its own seed.
+    pub fn new(sr: usize, nvoices: usize, preset: &VoicePreset) -> Self {
+        let voices = (0..nvoices)
+            .map(|i| {
+                VoiceBuilder::from_preset(sr, preset.clone())
+                    .seed(preset.seed.wrapping_add(i as u32 * 7919))
+                    .build()
+            })
+            .collect();
+
+        VoiceBank::from_voices(sr, voices)
+    }
+}
+
+impl<G: GlottalSource> VoiceBank<G> {
+    // wraps already configured voices, for example one per
+    // voice type for a choir
+    pub fn from_voices(sr: usize, voices: Vec<Voice<G>>) -> Self {
+        let voices: Vec<BankVoice<G>> = voices
+            .into_iter()
+            .map(|voice| {
+                let mut env = Envelope::new(sr);
+                env.set_attack(0.05);
+                env.set_release(0.3);
+                BankVoice {
+                    voice,
+                    env,
+                    gate: 0.0,
+                    level: 0.0,
+                    velocity: 0.0,
+                    note: None,
+                    stamp: 0,
+                    pitch: 0.0,
+                    pending_on: None,
+                    pending_off: None,
+                }
+            })
+            .collect();
+
+        VoiceBank {
+            stems: vec![0.0; voices.len()],
+            voices,
+            policy: StealPolicy::Oldest,
+            rng: LinearCongruentialGenerator::new(),
+            sr,
+            stamp: 0,
+            detune: 0.0,
+            humanize: 0.0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.voices.is_empty()
+    }
+
+    pub fn voice(&mut self, index: usize) -> &mut Voice<G> {
+        &mut self.voices[index].voice
+    }
+
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.policy = policy;
+    }
+
+    // envelope times, in seconds, for all voices
+    pub fn set_attack(&mut self, time: f32) {
+        for v in self.voices.iter_mut() {
+            v.env.set_attack(time);
+        }
+    }
+
+    pub fn set_release(&mut self, time: f32) {
+        for v in self.voices.iter_mut() {
+            v.env.set_release(time);
+        }
+    }
+
+    // maximum random detune per note, in cents
+    pub fn set_detune(&mut self, cents: f32) {
+        self.detune = cents.max(0.0);
+    }
+
+    // maximum random delay of note on/off events, in seconds
+    pub fn set_humanize(&mut self, time: f32) {
+        self.humanize = time.max(0.0);
+    }
+
+    pub fn seed(&mut self, seed: u32) {
+        self.rng.seed(seed);
+    }
+
+    fn humanize_delay(&mut self) -> Option<usize> {
+        let delay = self.rng.randf() * self.humanize * self.sr as f32;
+        Some(delay as usize)
+    }
+
+    fn find_voice(&self, note: f32) -> Option<usize> {
+        let voices = &self.voices;
+
+        // retrigger a voice already playing this note
+        if let Some(i) = voices.iter().position(|v| v.note == Some(note)) {
+            return Some(i);
+        }
+
+        // then a silent voice
+        if let Some(i) = voices.iter().position(|v| v.is_idle()) {
+            return Some(i);
+        }
+
+        // then the quietest of the released ones
+        let released = voices
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.note.is_none() && v.pending_on.is_none())
+            .min_by(|a, b| a.1.level.total_cmp(&b.1.level));
+
+        if let Some((i, _)) = released {
+            return Some(i);
+        }
+
+        // every voice is held, steal one
+        let held = voices.iter().enumerate();
+        let note_of = |v: &BankVoice<G>| v.note.unwrap_or(0.0);
+
+        let stolen = match self.policy {
+            StealPolicy::Oldest => held.min_by_key(|(_, v)| v.stamp),
+            StealPolicy::Quietest => held.min_by(|a, b| a.1.level.total_cmp(&b.1.level)),
+            StealPolicy::Lowest => held.min_by(|a, b| note_of(a.1).total_cmp(&note_of(b.1))),
+            StealPolicy::Highest => held.max_by(|a, b| note_of(a.1).total_cmp(&note_of(b.1))),
+            StealPolicy::None => None,
+        };
+
+        stolen.map(|(i, _)| i)
+    }
+
+    // Starts a note (a note number, fractional is fine) with a
+    // velocity of 0-1. Returns the voice used, or None if the
+    // note was dropped.
+    pub fn note_on(&mut self, note: f32, velocity: f32) -> Option<usize> {
+        let index = self.find_voice(note)?;
+
+        let detune = (self.rng.randf() * 2.0 - 1.0) * self.detune * 0.01;
+        let delay = self.humanize_delay();
+        self.stamp += 1;
+
+        let v = &mut self.voices[index];
+        v.note = Some(note);
+        v.velocity = velocity.clamp(0.0, 1.0);
+        v.pitch = note + detune;
+        v.stamp = self.stamp;
+        v.pending_on = delay;
+        v.pending_off = None;
+
+        Some(index)
+    }
+
+    pub fn note_off(&mut self, note: f32) {
+        let delay = self.humanize_delay();
+
+        if let Some(v) = self.voices.iter_mut().find(|v| v.note == Some(note)) {
+            v.note = None;
+
+            // a note that hasn't started yet still gets
+            // to sound briefly
+            v.pending_off = delay;
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        for v in self.voices.iter_mut() {
+            v.note = None;
+            v.pending_on = None;
+            v.pending_off = None;
+            v.gate = 0.0;
+        }
+    }
+
+    // number of voices currently holding a note
+    pub fn active_notes(&self) -> usize {
+        self.voices.iter().filter(|v| v.note.is_some()).count()
+    }
+
+    // Computes the next sample of every voice, returning
+    // them as separate stems (one per voice).
+    pub fn tick_stems(&mut self) -> &[f32] {
+        for (v, out) in self.voices.iter_mut().zip(self.stems.iter_mut()) {
+            if let Some(n) = v.pending_on {
+                if n == 0 {
+                    v.pending_on = None;
+                    v.gate = 1.0;
+                    v.voice.pitch = v.pitch;
+                    v.voice.onset();
+                } else {
+                    v.pending_on = Some(n - 1);
+                }
+            }
+
+            // note offs wait for the note on to happen first
+            if let (None, Some(n)) = (v.pending_on, v.pending_off) {
+                if n == 0 {
+                    v.pending_off = None;
+                    v.gate = 0.0;
+                } else {
+                    v.pending_off = Some(n - 1);
+                }
+            }
+
+            if v.gate == 0.0 && v.level < SILENCE {
+                // don't spend time on silent voices
+                v.level = 0.0;
+                *out = 0.0;
+                continue;
+            }
+
+            v.level = v.env.tick(v.gate);
+            *out = v.voice.tick() * v.level * v.velocity;
+        }
+
+        &self.stems
+    }
+
+    // computes the next sample, returning the mix of all voices
+    pub fn tick(&mut self) -> f32 {
+        self.tick_stems().iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bank(n: usize) -> VoiceBank {
+        VoiceBank::new(44100, n, &VoicePreset::default())
+    }
+
+    #[test]
+    fn test_allocation() {
+        let mut vb = bank(3);
+
+        assert_eq!(vb.note_on(60.0, 1.0), Some(0));
+        assert_eq!(vb.note_on(64.0, 1.0), Some(1));
+        assert_eq!(vb.note_on(67.0, 1.0), Some(2));
+        assert_eq!(vb.active_notes(), 3);
+
+        // same note goes back to the same voice
+        assert_eq!(vb.note_on(64.0, 0.5), Some(1));
+
+        // released voices are reused before stealing
+        vb.note_off(64.0);
+        for _ in 0..100 {
+            vb.tick();
+        }
+        assert_eq!(vb.note_on(72.0, 1.0), Some(1));
+    }
+
+    #[test]
+    fn test_stealing() {
+        let policies = [
+            (StealPolicy::Oldest, Some(0)),
+            (StealPolicy::Lowest, Some(1)),
+            (StealPolicy::Highest, Some(2)),
+            (StealPolicy::None, None),
+        ];
+
+        for (policy, expected) in policies {
+            let mut vb = bank(3);
+            vb.set_steal_policy(policy);
+            vb.note_on(60.0, 1.0);
+            vb.note_on(55.0, 1.0);
+            vb.note_on(67.0, 1.0);
+            assert_eq!(vb.note_on(70.0, 1.0), expected, "{:?}", policy);
+        }
+
+        // the voice that has been fading in the longest is loudest
+        let mut vb = bank(2);
+        vb.set_steal_policy(StealPolicy::Quietest);
+        vb.note_on(60.0, 1.0);
+        for _ in 0..1000 {
+            vb.tick();
+        }
+        vb.note_on(62.0, 1.0);
+        vb.tick();
+        assert_eq!(vb.note_on(64.0, 1.0), Some(1));
+    }
+
+    #[test]
+    fn test_humanize() {
+        let mut vb = bank(1);
+        vb.set_humanize(0.01);
+        vb.seed(1);
+        vb.note_on(60.0, 1.0);
+
+        // onset is delayed by at most 10ms
+        let mut onset = None;
+        for n in 0..1000 {
+            let out = vb.tick();
+            if onset.is_none() && out != 0.0 {
+                onset = Some(n);
+            }
+        }
+
+        assert!(onset.unwrap() <= 441);
+    }
+}